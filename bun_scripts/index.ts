@@ -1,122 +1,141 @@
-import { SorobanRpc, xdr, Address, nativeToScVal, scValToNative } from '@stellar/stellar-sdk';
-import type { Subprocess } from 'bun';
 import { $ } from 'bun';
 
-const MINER = 'GBDVX4VELCDSQ54KQJYTNHXAHFLBCA77ZY2USQBM4CSHTTV7DME7KALE';
-const CONTRACT_ID = 'CC5TSJ3E26YUYGYQKOBNJQLPX4XMUHUY7Q26JX53CJ2YUIZB5HVXXRV6';
-const MESSAGE = `KALE`;
+const MINER_BIN = '../target/release/fcm-miner-rust';
 
-const rpc = new SorobanRpc.Server(Bun.env.RPC_URL!);
+// Network, contract and miner come from the miner's config (FCM_CONFIG)
+const config = await $`${MINER_BIN} config show`.json();
 
-interface Data {
-    hash: string
-    current: bigint,
-    difficulty: number,
-    fcm: string,
-    finder: string,
-    is_nuked: boolean,
+if (!config.contract_id || !config.rpc_url || !config.network_passphrase) {
+    throw new Error('Set network and contract_id in the config named by FCM_CONFIG');
 }
 
-async function getContractData() {
-    try {
-        let result: Data = await rpc.getContractData(
-            CONTRACT_ID,
-            xdr.ScVal.scvLedgerKeyContractInstance()
-        ).then(({ val }) =>
-            val.contractData()
-                .val()
-                .instance()
-                .storage()?.[0].val()!
-        ).then((scval) => scValToNative(scval));
-
-        let blockLedgerKey = xdr.LedgerKey.contractData(
-            new xdr.LedgerKeyContractData({
-                contract: new Address(CONTRACT_ID).toScAddress(),
-                key: xdr.ScVal.scvVec([
-                    xdr.ScVal.scvSymbol("Block"),
-                    nativeToScVal(Number(result.current), { type: "u64" })
-                ]),
-                durability: xdr.ContractDataDurability.persistent(),
-            })
-        );
-
-        result.hash = await rpc.getLedgerEntries(blockLedgerKey)
-            .then(({ entries }) => entries?.[0].val.contractData().val())
-            .then((scval) => scValToNative(scval)?.hash?.toString('hex'))
-
-        return result;
-    } catch (err) {
-        console.error(err);
-    }
+const MINER: string = config.miner ?? 'GBDVX4VELCDSQ54KQJYTNHXAHFLBCA77ZY2USQBM4CSHTTV7DME7KALE';
+const CONTRACT_ID: string = config.contract_id;
+const PASSPHRASE: string = config.network_passphrase;
+// `config show` has filled in `{host}`/`{worker}`; the daemon fills in a MESSAGE template
+const MESSAGE = Bun.env.MESSAGE ?? config.message ?? `KALE`;
+
+const RPC_URL: string = Bun.env.RPC_URL ?? config.rpc_url;
+
+interface Block {
+    index: number,
+    prev_hash: string,
+    difficulty: number,
 }
 
-async function bootProc(result: Data) {
-    console.log('Proc booted');
+// One long-lived miner; new blocks are handed to it over its control API
+const DAEMON = Bun.env.DAEMON_URL ?? 'http://127.0.0.1:7879';
 
-    const proc = Bun.spawn([
-        '../target/release/fcm-miner-rust', 
-        '--index', (result.current + BigInt(1)).toString(),
-        '--prev-hash', result.hash,
-        '--target-zeros', result.difficulty.toString()
-    ], { stdout: 'pipe' })
+const proc = Bun.spawn([
+    MINER_BIN, 'daemon',
+    '--listen', new URL(DAEMON).host,
+    '--miner', MINER,
+    '--message', MESSAGE
+], { stdout: 'pipe' })
 
+let current_index: string | undefined
+
+async function readSolutions() {
     const reader = proc.stdout.getReader();
 
-    async function readStream() {
-        while (true) {
-            const { done, value } = await reader.read();
+    while (true) {
+        const { done, value } = await reader.read();
 
-            try {
-                await Bun.write(Bun.stdout, value!);
-            } catch(err) {
-                console.error(err);
-            }
+        if (done) break;
 
-            try {
-                let [nonce, hash] = JSON.parse(Buffer.from(value!).toString('utf-8'))
+        try {
+            await Bun.write(Bun.stdout, value!);
+        } catch(err) {
+            console.error(err);
+        }
 
-                try {
-                    await $`stellar contract invoke --id ${CONTRACT_ID} \
-                        --network vc \
-                        --source live \
-                        -- mine \
-                        --nonce ${nonce} \
-                        --hash ${hash} \
-                        --message ${MESSAGE} \
-                        --miner ${MINER}`
-                } catch (err) {
-                    console.error(err)
-                    clearInterval(interval)
-                }
+        let nonce, hash, message
 
-                break;
-            } catch {}
+        try {
+            [nonce, hash, message] = JSON.parse(Buffer.from(value!).toString('utf-8'))
+        } catch {
+            continue
+        }
 
-            if (done) break;
+        const index = current_index!;
+
+        try {
+            await $`stellar contract invoke --id ${CONTRACT_ID} \
+                --rpc-url ${RPC_URL} \
+                --network-passphrase ${PASSPHRASE} \
+                --source live \
+                -- mine \
+                --nonce ${nonce} \
+                --hash ${hash} \
+                --message ${message} \
+                --miner ${MINER}`
+
+            await $`${MINER_BIN} history submit --index ${index} --nonce ${nonce} --status confirmed`
+        } catch (err) {
+            console.error(err)
+            await $`${MINER_BIN} history submit --index ${index} --nonce ${nonce} --status failed`.nothrow()
+            watcher.kill()
         }
     }
+}
 
-    readStream();
+readSolutions();
 
-    return proc
+async function postJob(block: Block) {
+    const res = await fetch(`${DAEMON}/job`, {
+        method: 'POST',
+        body: JSON.stringify(block),
+    })
+
+    if (!res.ok) {
+        throw new Error(`Daemon rejected job: ${await res.text()}`)
+    }
+
+    current_index = block.index.toString()
 }
 
-let prev_result: Data | undefined
-let proc: Subprocess | undefined
+// The miner follows the contract's events and prints a line per new block,
+// failing over between the config's rpc_urls unless RPC_URL pins one
+const watcher = Bun.spawn([MINER_BIN, 'watch'], {
+    stdout: 'pipe',
+    env: Bun.env.RPC_URL ? { ...Bun.env, FCM_RPC_URL: Bun.env.RPC_URL } : Bun.env,
+})
 
-const interval = setInterval(async () => {
-    let result = await getContractData()
+async function readBlocks() {
+    const decoder = new TextDecoder();
+    let buffered = '';
 
-    if (result && result.hash !== prev_result?.hash) {
-        if (proc) {
-            proc.kill()
-        }
+    for await (const chunk of watcher.stdout) {
+        buffered += decoder.decode(chunk, { stream: true });
 
-        console.log(result.current + BigInt(1), result.difficulty, result.hash);
-        prev_result = result
-    }
+        let lines = buffered.split('\n');
+        buffered = lines.pop()!;
+
+        for (const line of lines) {
+            let block: Block
+
+            try {
+                block = JSON.parse(line)
+            } catch {
+                continue
+            }
+
+            if (block.index === undefined) continue
+
+            console.log(block.index, block.difficulty, block.prev_hash);
 
-    if (result && (!proc || proc.killed)) {
-        proc = await bootProc(result)
+            // The daemon may still be starting, so retry a few times
+            for (let attempt = 0; attempt < 10; attempt++) {
+                try {
+                    await postJob(block)
+                    break
+                } catch (err) {
+                    console.error(err)
+                    await Bun.sleep(1000)
+                }
+            }
+        }
     }
-}, 1000)
\ No newline at end of file
+}
+
+readBlocks();
@@ -0,0 +1,114 @@
+#ifndef FCM_MINER_H
+#define FCM_MINER_H
+
+/* Generated by cbindgen from src/ffi.rs; do not edit. */
+
+#include <stdint.h>
+
+#define FCM_EVENT_HASHRATE 1
+
+#define FCM_EVENT_BEST 2
+
+#define FCM_EVENT_SOLUTION 3
+
+#define FCM_EVENT_EXHAUSTED 4
+
+/**
+ * Opaque block template.
+ */
+typedef struct FcmJob FcmJob;
+
+/**
+ * Opaque running search.
+ */
+typedef struct FcmMiner FcmMiner;
+
+/**
+ * One event, filled in by `fcm_poll_event`. Fields a kind does not use are zero.
+ */
+typedef struct FcmEvent {
+  /**
+   * One of the `FCM_EVENT_*` constants.
+   */
+  uint32_t kind;
+  uint32_t zeros;
+  uint64_t nonce;
+  uint64_t hashes;
+  /**
+   * H/s since the previous hashrate event.
+   */
+  double rate;
+  uint8_t hash[32];
+} FcmEvent;
+
+/**
+ * A job from a 32-byte `prev_hash`, a `G...` miner address and a NUL-terminated
+ * UTF-8 message. Returns NULL if any argument is invalid.
+ *
+ * # Safety
+ * `prev_hash` must point to 32 readable bytes; `miner` and `message` must be
+ * NUL-terminated strings.
+ */
+FcmJob *fcm_job_new(uint64_t index,
+                    const uint8_t *prev_hash,
+                    uint32_t difficulty,
+                    const char *miner,
+                    const char *message);
+
+/**
+ * # Safety
+ * `job` must come from `fcm_job_new` and not be used afterwards. NULL is ignored.
+ */
+void fcm_job_free(FcmJob *job);
+
+/**
+ * Start searching `nonce_start..=nonce_end` on `threads` workers (0 = all
+ * CPUs). The job is copied. Returns NULL if the range is empty, the job is
+ * NULL or the hashing self-test fails.
+ *
+ * # Safety
+ * `job` must be NULL or come from `fcm_job_new`.
+ */
+FcmMiner *fcm_mine_start(const FcmJob *job,
+                         uint32_t threads,
+                         uint64_t nonce_start,
+                         uint64_t nonce_end);
+
+/**
+ * Take the next event without blocking. Returns 1 when `out` was filled, 0
+ * when nothing is pending yet and -1 once the search is over and drained.
+ *
+ * # Safety
+ * `miner` must come from `fcm_mine_start`; `out` must be writable.
+ */
+int32_t fcm_poll_event(FcmMiner *miner, FcmEvent *out);
+
+/**
+ * Ask the workers to stop after their current batch; events already queued
+ * can still be polled.
+ *
+ * # Safety
+ * `miner` must be NULL or come from `fcm_mine_start`.
+ */
+void fcm_cancel(const FcmMiner *miner);
+
+/**
+ * Cancel the search if it is still running and release the handle. The
+ * workers wind down in the background.
+ *
+ * # Safety
+ * `miner` must come from `fcm_mine_start` and not be used afterwards. NULL is ignored.
+ */
+void fcm_miner_free(FcmMiner *miner);
+
+/**
+ * Hash `nonce` for `job` into `out_hash` (if not NULL). Returns 1 if it solves
+ * the job, 0 if not and -1 if `job` is NULL.
+ *
+ * # Safety
+ * `job` must be NULL or come from `fcm_job_new`; `out_hash` must be NULL or
+ * point to 32 writable bytes.
+ */
+int32_t fcm_verify(const FcmJob *job, uint64_t nonce, uint8_t *out_hash);
+
+#endif /* FCM_MINER_H */
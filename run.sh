@@ -1,7 +1,18 @@
 HASH=000000004e526b039cca2c8fe02af11e6d167387e976ab53b0aff6bf5248c570
 NONCE=7424281576
 
-stellar contract invoke --id CC5TSJ3E26YUYGYQKOBNJQLPX4XMUHUY7Q26JX53CJ2YUIZB5HVXXRV6 \
+# Contract, network, miner and message as the miner resolves them: FCM_* variables,
+# then the config file (see miner.example.toml), with the message filled in
+MINER_BIN=${MINER_BIN:-./target/release/fcm-miner-rust}
+export FCM_CONFIG=${FCM_CONFIG:-miner.toml}
+
+setting() {
+    "$MINER_BIN" config get "$1"
+}
+
+stellar contract invoke --id "$(setting contract_id)" \
     --source live \
-    --network vc -- mine --hash $HASH --message KALE --nonce $NONCE \
-    --miner GBDVX4VELCDSQ54KQJYTNHXAHFLBCA77ZY2USQBM4CSHTTV7DME7KALE
\ No newline at end of file
+    --rpc-url "$(setting rpc_url)" \
+    --network-passphrase "$(setting network_passphrase)" \
+    -- mine --hash $HASH --message "$(setting message)" --nonce $NONCE \
+    --miner "$(setting miner)"
@@ -0,0 +1,106 @@
+//! Async mining for embedding in tokio applications.
+//!
+//! Work runs on the same worker threads as the CLI; cancelling the token or
+//! dropping the event stream stops them within one batch.
+
+use futures_core::Stream;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::thread;
+use std::time::Duration;
+use tokio::sync::mpsc::{self, UnboundedReceiver};
+use tokio_util::sync::{CancellationToken, DropGuard};
+
+use crate::job::Job;
+use crate::session::Session;
+
+pub use crate::session::{MineOptions, MinerEvent, Solution};
+
+// How often the canceller checks the token
+const CANCEL_POLL: Duration = Duration::from_millis(10);
+
+#[derive(Debug, PartialEq)]
+pub enum MineError {
+    Cancelled,
+    Exhausted { hashes: u64 },
+    /// An engine failed its known-answer test, so no work was started.
+    SelfTest(String),
+}
+
+impl std::fmt::Display for MineError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MineError::Cancelled => f.write_str("mining was cancelled"),
+            MineError::Exhausted { hashes } => write!(f, "range exhausted after {} hashes", hashes),
+            MineError::SelfTest(err) => write!(f, "self-test failed: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for MineError {}
+
+/// Mine until a solution is found, the range runs out or `cancel` fires.
+pub async fn mine(job: Job, opts: MineOptions, cancel: CancellationToken) -> Result<Solution, MineError> {
+    let mut events = events(job, opts, cancel)?;
+
+    while let Some(event) = events.recv().await {
+        match event {
+            MinerEvent::Solution(solution) => return Ok(solution),
+            MinerEvent::Exhausted { hashes } => return Err(MineError::Exhausted { hashes }),
+            _ => {}
+        }
+    }
+
+    Err(MineError::Cancelled)
+}
+
+/// Progress, best hashes and the solution as they happen. The stream ends after
+/// a solution, exhaustion or cancellation; dropping it cancels the search.
+pub fn events(job: Job, opts: MineOptions, cancel: CancellationToken) -> Result<MinerEvents, MineError> {
+    let (tx, rx) = mpsc::unbounded_channel();
+
+    let session = Session::start(job, opts, move |event| {
+        let _ = tx.send(event);
+    })
+    .map_err(MineError::SelfTest)?;
+
+    // A child token lets the stream cancel its own search without touching the caller's
+    let cancel = cancel.child_token();
+
+    // Waits for the token without a runtime, so the API works from any executor
+    thread::spawn({
+        let cancel = cancel.clone();
+
+        move || {
+            while !cancel.is_cancelled() && !session.is_stopping() {
+                thread::sleep(CANCEL_POLL);
+            }
+
+            session.cancel();
+        }
+    });
+
+    Ok(MinerEvents {
+        rx,
+        _cancel: cancel.drop_guard(),
+    })
+}
+
+pub struct MinerEvents {
+    rx: UnboundedReceiver<MinerEvent>,
+    _cancel: DropGuard,
+}
+
+impl MinerEvents {
+    pub async fn recv(&mut self) -> Option<MinerEvent> {
+        self.rx.recv().await
+    }
+}
+
+impl Stream for MinerEvents {
+    type Item = MinerEvent;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<MinerEvent>> {
+        self.rx.poll_recv(cx)
+    }
+}
@@ -0,0 +1,419 @@
+//! `--config miner.toml`: settings shared by every run, in `[defaults]` and
+//! named `[profiles.<name>]` tables.
+//!
+//! File values only replace the built-in defaults. Highest first, a setting
+//! comes from its `FCM_*` environment variable, then the command line, then the
+//! profile, then `[defaults]`, so a deployment can pin a value whatever flags
+//! the scripts it runs pass. `[networks.<name>]` tables define networks beyond
+//! the built-in mainnet, testnet and futurenet.
+
+use clap::parser::ValueSource;
+use clap::{ArgMatches, Command as ClapCommand, Subcommand};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+use crate::error::MinerError;
+use crate::network::{self, Network};
+
+/// One profile's worth of settings; anything left out keeps its default.
+#[derive(Clone, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Settings {
+    /// A built-in network or one from `[networks]`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub network: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rpc_url: Option<String>,
+    /// Several endpoints to fail over between; `rpc_url` wins when both are set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rpc_urls: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub race_send: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub health_interval: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub poll_interval: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub network_passphrase: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub contract_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub miner: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub worker: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub threads: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub batch_size: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub engine: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cpu_percent: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_hashrate: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub idle: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub order: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub eta_window: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tui: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub history: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub log_level: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub log_format: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub log_file: Option<PathBuf>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct File {
+    /// Profile used when `--config-profile` does not pick one.
+    profile: Option<String>,
+    #[serde(default)]
+    defaults: Settings,
+    #[serde(default)]
+    profiles: BTreeMap<String, Settings>,
+    #[serde(default)]
+    networks: BTreeMap<String, Network>,
+}
+
+/// The resolved settings and the networks the file defines.
+#[derive(Default)]
+pub struct Config {
+    pub settings: Settings,
+    networks: BTreeMap<String, Network>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Print the settings a run would mine with as JSON, the message filled in
+    Show,
+    /// Print one of those settings, e.g. `contract_id`, for shell scripts
+    Get { key: String },
+}
+
+impl Settings {
+    // Fields set in `over` win
+    fn overlay(self, over: Settings) -> Settings {
+        Settings {
+            network: over.network.or(self.network),
+            rpc_url: over.rpc_url.or(self.rpc_url),
+            rpc_urls: over.rpc_urls.or(self.rpc_urls),
+            race_send: over.race_send.or(self.race_send),
+            health_interval: over.health_interval.or(self.health_interval),
+            poll_interval: over.poll_interval.or(self.poll_interval),
+            network_passphrase: over.network_passphrase.or(self.network_passphrase),
+            contract_id: over.contract_id.or(self.contract_id),
+            miner: over.miner.or(self.miner),
+            message: over.message.or(self.message),
+            worker: over.worker.or(self.worker),
+            threads: over.threads.or(self.threads),
+            batch_size: over.batch_size.or(self.batch_size),
+            engine: over.engine.or(self.engine),
+            cpu_percent: over.cpu_percent.or(self.cpu_percent),
+            max_hashrate: over.max_hashrate.or(self.max_hashrate),
+            idle: over.idle.or(self.idle),
+            order: over.order.or(self.order),
+            eta_window: over.eta_window.or(self.eta_window),
+            tui: over.tui.or(self.tui),
+            history: over.history.or(self.history),
+            log_level: over.log_level.or(self.log_level),
+            log_format: over.log_format.or(self.log_format),
+            log_file: over.log_file.or(self.log_file),
+        }
+    }
+
+    // Settings with no flag of their own can still be overridden from the environment
+    fn with_env(mut self) -> Settings {
+        let var = |name| std::env::var(name).ok().filter(|value: &String| !value.is_empty());
+
+        self.network = var("FCM_NETWORK").or(self.network);
+        self.rpc_url = var("FCM_RPC_URL").or(self.rpc_url);
+        self.rpc_urls = var("FCM_RPC_URLS")
+            .map(|urls| urls.split(',').map(|url| url.trim().to_string()).collect())
+            .or(self.rpc_urls);
+        self.network_passphrase = var("FCM_NETWORK_PASSPHRASE").or(self.network_passphrase);
+        self.contract_id = var("FCM_CONTRACT_ID").or(self.contract_id);
+        self
+    }
+
+    // Argument ids and the values to default them to
+    fn defaults(&self) -> Vec<(&'static str, String)> {
+        let path = |path: &PathBuf| path.display().to_string();
+
+        [
+            ("miner", self.miner.clone()),
+            ("message", self.message.clone()),
+            ("worker", self.worker.clone()),
+            ("threads", self.threads.map(|n| n.to_string())),
+            ("batch_size", self.batch_size.map(|n| n.to_string())),
+            ("engine", self.engine.clone()),
+            ("cpu_percent", self.cpu_percent.map(|n| n.to_string())),
+            ("max_hashrate", self.max_hashrate.map(|n| n.to_string())),
+            ("idle", self.idle.map(|on| on.to_string())),
+            ("order", self.order.clone()),
+            ("eta_window", self.eta_window.map(|n| n.to_string())),
+            ("race_send", self.race_send.map(|on| on.to_string())),
+            ("health_interval", self.health_interval.map(|n| n.to_string())),
+            ("poll_interval", self.poll_interval.map(|n| n.to_string())),
+            ("tui", self.tui.map(|on| on.to_string())),
+            ("history", self.history.as_ref().map(path)),
+            ("log_level", self.log_level.clone()),
+            ("log_format", self.log_format.clone()),
+            ("log_file", self.log_file.as_ref().map(path)),
+        ]
+        .into_iter()
+        .filter_map(|(id, value)| Some((id, value?)))
+        .collect()
+    }
+}
+
+/// Settings from the file named by `--config` (or `FCM_CONFIG`), merged over
+/// `[defaults]` for the chosen profile. Empty when there is no file.
+pub fn load(args: &[OsString]) -> Result<Config, MinerError> {
+    // Read before clap runs, since the file supplies clap's defaults
+    let path = env("FCM_CONFIG").or_else(|| flag(args, "--config"));
+    let profile = env("FCM_CONFIG_PROFILE")
+        .or_else(|| flag(args, "--config-profile"))
+        .map(|name| name.to_string_lossy().into_owned());
+
+    let Some(path) = path else {
+        return match profile {
+            Some(name) => Err(MinerError::Config(format!("--config-profile {} needs a --config file", name))),
+            None => Ok(Config {
+                settings: Settings::default().with_env(),
+                networks: BTreeMap::new(),
+            }),
+        };
+    };
+
+    let file = read(Path::new(&path))?;
+    let profile = profile.or(file.profile);
+
+    let settings = match profile {
+        Some(name) => {
+            let Some(over) = file.profiles.get(&name) else {
+                let known: Vec<_> = file.profiles.keys().map(String::as_str).collect();
+                return Err(MinerError::Config(format!(
+                    "no profile {} in {} (has: {})",
+                    name,
+                    Path::new(&path).display(),
+                    known.join(", ")
+                )));
+            };
+
+            file.defaults.overlay(over.clone())
+        }
+        None => file.defaults,
+    };
+
+    let networks = file
+        .networks
+        .into_iter()
+        .map(|(name, mut network)| {
+            network.name = name.clone();
+            (name, network)
+        })
+        .collect();
+
+    Ok(Config {
+        settings: settings.with_env(),
+        networks,
+    })
+}
+
+impl Config {
+    /// The network mined on, with `rpc_url(s)`, `network_passphrase` and
+    /// `contract_id` applied over its own values. `None` when nothing names one.
+    pub fn network(&self) -> Result<Option<Network>, MinerError> {
+        let settings = &self.settings;
+
+        let mut network = match settings.network.as_deref() {
+            Some(name) => match self.networks.get(name).cloned().or_else(|| Network::builtin(name)) {
+                Some(network) => network,
+                None => {
+                    let known: Vec<_> = network::BUILTIN
+                        .iter()
+                        .copied()
+                        .chain(self.networks.keys().map(String::as_str))
+                        .collect();
+                    return Err(MinerError::Config(format!("unknown network {} (has: {})", name, known.join(", "))));
+                }
+            },
+            // A network spelled out entirely in settings
+            None if settings.network_passphrase.is_some() => Network {
+                name: String::from("custom"),
+                ..Network::default()
+            },
+            None => return Ok(None),
+        };
+
+        if let Some(passphrase) = &settings.network_passphrase {
+            network.passphrase = passphrase.clone();
+        }
+
+        if let Some(rpc_url) = &settings.rpc_url {
+            network.rpc_urls = vec![rpc_url.clone()];
+        } else if let Some(rpc_urls) = &settings.rpc_urls {
+            network.rpc_urls = rpc_urls.clone();
+        }
+
+        if let Some(contract_id) = &settings.contract_id {
+            network.contract_id = Some(contract_id.clone());
+        }
+
+        if network.contract_id.is_some() {
+            network.contract()?;
+        }
+
+        Ok(Some(network))
+    }
+}
+
+fn read(path: &Path) -> Result<File, MinerError> {
+    let text = std::fs::read_to_string(path).map_err(|err| MinerError::io(path, err))?;
+
+    toml::from_str(&text).map_err(|err| MinerError::Config(format!("{}: {}", path.display(), err.message())))
+}
+
+fn env(name: &str) -> Option<OsString> {
+    std::env::var_os(name).filter(|value| !value.is_empty())
+}
+
+// `--name value` or `--name=value`, wherever it appears
+fn flag(args: &[OsString], name: &str) -> Option<OsString> {
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        let arg = arg.to_string_lossy();
+
+        if arg == "--" {
+            break;
+        }
+
+        if arg == name {
+            return args.next().cloned();
+        }
+
+        if let Some(value) = arg.strip_prefix(name).and_then(|rest| rest.strip_prefix('=')) {
+            return Some(value.into());
+        }
+    }
+
+    None
+}
+
+/// Make `settings` the defaults of every argument they name, in `command`
+/// and all of its subcommands.
+pub fn apply(command: ClapCommand, config: &Config) -> ClapCommand {
+    let defaults = config.settings.defaults();
+
+    apply_defaults(command, &defaults)
+}
+
+/// Parse `argv` with `command`, letting a set `FCM_*` variable win over the
+/// flag it stands for. Clap itself prefers the flag, so any argument given both
+/// ways is parsed again from the variable alone.
+pub fn parse(command: ClapCommand, argv: &[OsString]) -> ArgMatches {
+    let matches = command.clone().get_matches_from(argv);
+    let (command, overridden) = prefer_env(command, &matches);
+
+    if overridden {
+        command.get_matches_from(argv)
+    } else {
+        matches
+    }
+}
+
+// Hand the flags of arguments whose variable is set to hidden copies, so the
+// arguments themselves only see the variable
+fn prefer_env(mut command: ClapCommand, matches: &ArgMatches) -> (ClapCommand, bool) {
+    let given: Vec<_> = command
+        .get_arguments()
+        .filter(|arg| arg.get_env().is_some_and(|name| env(&name.to_string_lossy()).is_some()))
+        .filter(|arg| matches.value_source(arg.get_id().as_str()) == Some(ValueSource::CommandLine))
+        .cloned()
+        .collect();
+    let mut overridden = !given.is_empty();
+
+    for arg in given {
+        // Parsed at most twice per run, so leaking the few names is harmless
+        let id = arg.get_id().to_string();
+        let flag: &'static str = Box::leak(format!("{}-flag", id).into_boxed_str());
+        let long: &'static str = Box::leak(format!("{}-from-env", arg.get_long().unwrap_or(&id)).into_boxed_str());
+
+        command = command
+            .mut_arg(&id, |arg| arg.long(long).short(None).hide(true))
+            .arg(arg.id(flag).env(None).hide(true));
+    }
+
+    if let Some((name, sub_matches)) = matches.subcommand() {
+        let name = name.to_string();
+        let mut changed = false;
+
+        command = command.mut_subcommand(name, |sub| {
+            let (sub, sub_changed) = prefer_env(sub, sub_matches);
+            changed = sub_changed;
+            sub
+        });
+        overridden |= changed;
+    }
+
+    (command, overridden)
+}
+
+fn apply_defaults(mut command: ClapCommand, defaults: &[(&'static str, String)]) -> ClapCommand {
+    for (id, value) in defaults {
+        if command.get_arguments().any(|arg| arg.get_id() == id) {
+            // Parsed once per run, so leaking the few values is harmless
+            let value: &'static str = Box::leak(value.clone().into_boxed_str());
+            command = command.mut_arg(*id, |arg| arg.default_value(value));
+        }
+    }
+
+    let names: Vec<String> = command.get_subcommands().map(|sub| sub.get_name().to_string()).collect();
+
+    for name in names {
+        command = command.mut_subcommand(name, |sub| apply_defaults(sub, defaults));
+    }
+
+    command
+}
+
+/// Print settings as mining resolves them: `resolved` holds what the parsed
+/// arguments came to, the rest is read from the file and environment here.
+pub fn run(config: &Config, resolved: Settings, action: &Command) -> Result<(), MinerError> {
+    let mut settings = config.settings.clone().overlay(resolved);
+
+    // Scripts read the network's values through the same keys
+    if let Some(network) = config.network()? {
+        settings.network = Some(network.name);
+        settings.network_passphrase = Some(network.passphrase);
+        settings.rpc_url = network.rpc_urls.first().cloned();
+        settings.rpc_urls = Some(network.rpc_urls);
+        settings.contract_id = network.contract_id;
+    }
+
+    let value = serde_json::to_value(&settings).unwrap_or_default();
+
+    match action {
+        Command::Show => println!("{}", serde_json::to_string_pretty(&value).unwrap_or_default()),
+        Command::Get { key } => match value.get(key) {
+            Some(Value::String(text)) => println!("{}", text),
+            Some(other) => println!("{}", other),
+            None => return Err(MinerError::NotFound(format!("{} is not set", key))),
+        },
+    }
+
+    Ok(())
+}
@@ -0,0 +1,479 @@
+//! Long-running miner controlled over a small HTTP/JSON API, so scripts can
+//! switch jobs without restarting the process.
+//!
+//! `GET /status`, `POST /job`, `POST /pause`, `POST /resume`, `POST /threads`,
+//! `POST /throttle` (`cpu_percent`, `max_hashrate` with 0 uncapped, and `idle`;
+//! any left out keep their value) and `GET /history`. Hashrates are in MH/s, as
+//! on the command line. Solutions are still printed to stdout as
+//! `[nonce,"hash","message"]`. With `--watch` the daemon also follows the
+//! contract itself and switches jobs as soon as a block is mined.
+
+use clap::Args;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Instant;
+use tracing::{error, info};
+
+use crate::error::MinerError;
+use crate::history::{self, Solve};
+use crate::job::{self, Job};
+use crate::miner::{Miner, NonceRange, Outcome};
+use crate::network::Network;
+use crate::rpc::Watcher;
+use crate::strkey;
+
+// Larger bodies are not jobs
+const MAX_BODY: usize = 64 * 1024;
+
+#[derive(Args)]
+pub struct DaemonArgs {
+    /// Address to serve the control API on
+    #[arg(long, default_value = "127.0.0.1:7879")]
+    pub listen: SocketAddr,
+
+    /// Serve on this Unix socket instead of TCP
+    #[cfg(unix)]
+    #[arg(long)]
+    pub socket: Option<PathBuf>,
+
+    /// Miner address for jobs that do not name one
+    #[arg(long, env = "FCM_MINER", default_value = crate::MINER)]
+    pub miner: String,
+
+    /// Message for jobs that do not carry one; may use `{host}` and `{worker}`
+    #[arg(long, env = "FCM_MESSAGE", default_value = crate::MESSAGE)]
+    pub message: String,
+
+    /// Name substituted for `{worker}` (defaults to the hostname)
+    #[arg(long, env = "FCM_WORKER")]
+    pub worker: Option<String>,
+
+    /// Take jobs from the configured network's contract as well as the API
+    #[arg(long)]
+    pub watch: bool,
+
+    #[command(flatten)]
+    pub rpc: crate::watch::RpcArgs,
+
+    #[command(flatten)]
+    pub miner_args: crate::MinerArgs,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum State {
+    Idle,
+    Mining,
+    Solved,
+    Exhausted,
+}
+
+impl State {
+    fn name(&self) -> &'static str {
+        match self {
+            State::Idle => "idle",
+            State::Mining => "mining",
+            State::Solved => "solved",
+            State::Exhausted => "exhausted",
+        }
+    }
+}
+
+struct Daemon {
+    miner: Arc<Miner>,
+    history: Option<PathBuf>,
+    job: Option<Job>,
+    state: State,
+    solution: Option<Value>,
+    started: Instant,
+    // Stats total when the job began, as the stats count across jobs
+    base_hashes: u64,
+    // Bumped per job so a stopped search cannot report into the next one
+    generation: u64,
+    stop: Arc<AtomicBool>,
+    search: Option<JoinHandle<()>>,
+}
+
+#[derive(Deserialize)]
+struct Threads {
+    threads: usize,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ThrottleSettings {
+    cpu_percent: Option<u32>,
+    // MH/s, like `--max-hashrate`
+    max_hashrate: Option<f64>,
+    idle: Option<bool>,
+}
+
+pub fn run(args: &DaemonArgs, history: Option<&Path>, network: Option<Network>) -> Result<(), MinerError> {
+    // Fail on a bad network before starting any workers
+    let watcher = match args.watch {
+        true => Some(args.rpc.watcher(network.as_ref())?),
+        false => None,
+    };
+
+    let miner = crate::start_miner(&args.miner_args, history);
+
+    if let Some((_, endpoints)) = &watcher {
+        miner.stats.set_rpc(endpoints.clone());
+    }
+
+    let daemon = Arc::new(Mutex::new(Daemon {
+        miner,
+        history: history.map(Path::to_path_buf),
+        job: None,
+        state: State::Idle,
+        solution: None,
+        started: Instant::now(),
+        base_hashes: 0,
+        generation: 0,
+        stop: Arc::new(AtomicBool::new(true)),
+        search: None,
+    }));
+
+    if let Some((watcher, _)) = watcher {
+        spawn_watcher(watcher, daemon.clone(), args);
+    }
+
+    #[cfg(unix)]
+    if let Some(path) = &args.socket {
+        // A socket left by an earlier run would make the bind fail
+        let _ = std::fs::remove_file(path);
+
+        let listener = std::os::unix::net::UnixListener::bind(path).map_err(|err| MinerError::io(path, err))?;
+
+        info!("Control API on {}", path.display());
+
+        for stream in listener.incoming().flatten() {
+            spawn_handler(stream, daemon.clone(), args);
+        }
+
+        return Ok(());
+    }
+
+    let listener = TcpListener::bind(args.listen).map_err(|err| MinerError::Io {
+        target: args.listen.to_string(),
+        source: err,
+    })?;
+
+    info!("Control API on http://{}", args.listen);
+
+    for stream in listener.incoming().flatten() {
+        spawn_handler(stream, daemon.clone(), args);
+    }
+
+    Ok(())
+}
+
+fn spawn_handler(stream: impl Read + Write + Send + 'static, daemon: Arc<Mutex<Daemon>>, args: &DaemonArgs) {
+    let defaults = (args.miner.clone(), args.message.clone(), args.worker.clone());
+
+    thread::spawn(move || {
+        if let Err(err) = handle(stream, &daemon, &defaults) {
+            error!("Control request failed: {}", err);
+        }
+    });
+}
+
+fn spawn_watcher(mut watcher: Watcher, daemon: Arc<Mutex<Daemon>>, args: &DaemonArgs) {
+    let defaults = (args.miner.clone(), args.message.clone(), args.worker.clone());
+    let interval = args.rpc.interval();
+
+    thread::spawn(move || {
+        let stop = AtomicBool::new(false);
+
+        let result = watcher.run(interval, &stop, |block| match job_from_json(block.to_json(), &defaults) {
+            Ok(job) => {
+                set_job(&daemon, job);
+            }
+            Err(err) => crate::report(&err),
+        });
+
+        // Jobs can still arrive over the API, also once a replay has ended
+        match result {
+            Ok(()) | Err(MinerError::ReplayEnded(_)) => {}
+            Err(err) => crate::report(&err),
+        }
+    });
+}
+
+fn handle(
+    mut stream: impl Read + Write,
+    daemon: &Arc<Mutex<Daemon>>,
+    defaults: &(String, String, Option<String>),
+) -> io::Result<()> {
+    let (method, path, body) = read_request(&mut stream)?;
+
+    let (status, reply) = match (method.as_str(), path.as_str()) {
+        ("GET", "/status") => (200, status(&daemon.lock().unwrap())),
+        ("POST", "/job") => match parse_job(&body, defaults) {
+            Ok(job) => (200, set_job(daemon, job)),
+            Err(err) => (400, err.to_event()),
+        },
+        ("POST", "/pause") | ("POST", "/resume") => {
+            let daemon = daemon.lock().unwrap();
+            daemon.miner.throttle.set_paused(path == "/pause");
+            (200, status(&daemon))
+        }
+        ("POST", "/threads") => {
+            let daemon = daemon.lock().unwrap();
+
+            // Only the workers started with the daemon exist to be woken
+            match serde_json::from_slice::<Threads>(&body) {
+                Ok(Threads { threads }) if (1..=daemon.miner.threads).contains(&threads) => {
+                    daemon.miner.throttle.set_active_threads(threads);
+                    (200, status(&daemon))
+                }
+                Ok(_) => (
+                    400,
+                    json!({ "error": format!("threads must be between 1 and {}", daemon.miner.threads) }),
+                ),
+                Err(err) => (400, json!({ "error": err.to_string() })),
+            }
+        }
+        ("POST", "/throttle") => {
+            let daemon = daemon.lock().unwrap();
+            let throttle = &daemon.miner.throttle;
+
+            match serde_json::from_slice::<ThrottleSettings>(&body) {
+                Ok(settings) if settings.cpu_percent.is_some_and(|percent| !(1..=100).contains(&percent)) => {
+                    (400, json!({ "error": "cpu_percent must be between 1 and 100" }))
+                }
+                Ok(settings) if settings.max_hashrate.is_some_and(|mhs| !mhs.is_finite() || mhs < 0.0) => {
+                    (400, json!({ "error": "max_hashrate must be 0 or more MH/s" }))
+                }
+                Ok(settings) => {
+                    if let Some(percent) = settings.cpu_percent {
+                        throttle.set_cpu_percent(percent);
+                    }
+
+                    if let Some(mhs) = settings.max_hashrate {
+                        throttle.set_max_hashrate((mhs * 1_000_000.0) as u64);
+                    }
+
+                    if let Some(idle) = settings.idle {
+                        throttle.set_idle(idle);
+                    }
+
+                    (200, status(&daemon))
+                }
+                Err(err) => (400, json!({ "error": err.to_string() })),
+            }
+        }
+        ("GET", "/history") => {
+            let path = daemon.lock().unwrap().history.clone();
+
+            match path.map(|path| history::load(&path)) {
+                Some(Ok(solves)) => (200, json!(solves)),
+                Some(Err(err)) => (500, json!({ "error": err.to_string() })),
+                None => (200, json!([])),
+            }
+        }
+        (_, "/status" | "/job" | "/pause" | "/resume" | "/threads" | "/throttle" | "/history") => {
+            (405, json!({ "error": "method not allowed" }))
+        }
+        _ => (404, json!({ "error": "not found" })),
+    };
+
+    let body = reply.to_string();
+    let reason = match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        _ => "Internal Server Error",
+    };
+
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        reason,
+        body.len(),
+        body
+    )
+}
+
+// Method, path without the query string, and body
+fn read_request(stream: &mut impl Read) -> io::Result<(String, String, Vec<u8>)> {
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+
+    let mut words = line.split_whitespace();
+    let method = words.next().unwrap_or_default().to_string();
+    let path = words.next().unwrap_or_default();
+    let path = path.split('?').next().unwrap_or_default().to_string();
+
+    let mut length = 0;
+
+    loop {
+        let mut header = String::new();
+
+        if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+            break;
+        }
+
+        if let Some((name, value)) = header.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                length = value.trim().parse().unwrap_or(0);
+            }
+        }
+    }
+
+    if length > MAX_BODY {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "request body too large"));
+    }
+
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+
+    Ok((method, path, body))
+}
+
+// Jobs may leave out the miner and message to use the daemon's defaults
+fn parse_job(body: &[u8], defaults: &(String, String, Option<String>)) -> Result<Job, MinerError> {
+    let job = serde_json::from_slice(body).map_err(|err| MinerError::InvalidJob(err.to_string()))?;
+
+    job_from_json(job, defaults)
+}
+
+fn job_from_json(job: Value, (miner, message, worker): &(String, String, Option<String>)) -> Result<Job, MinerError> {
+    job::from_json(job, miner, message, worker.as_deref())
+}
+
+fn status(daemon: &Daemon) -> Value {
+    let rates = daemon.miner.stats.rates();
+    let throttle = &daemon.miner.throttle;
+
+    json!({
+        "state": daemon.state.name(),
+        "paused": throttle.is_paused(),
+        "threads": throttle.active_threads(),
+        "max_threads": daemon.miner.threads,
+        "cpu_percent": throttle.cpu_percent(),
+        "max_hashrate": throttle.max_hashrate() as f64 / 1_000_000.0,
+        "idle": throttle.is_idle(),
+        "job": daemon.job.as_ref().map(|job| json!({
+            "index": job.index,
+            "prev_hash": hex::encode(job.prev_hash),
+            "difficulty": job.difficulty,
+            "message": job.message,
+        })),
+        "seconds": daemon.started.elapsed().as_secs_f64(),
+        "hashes": daemon.miner.stats.snapshot().total() - daemon.base_hashes,
+        "hashrate": rates.total / 1_000_000.0,
+        "best_zeros": daemon.miner.stats.best_zeros(),
+        "solution": daemon.solution,
+        "rpc": daemon.miner.stats.rpc_health(),
+    })
+}
+
+// Stop the current search and start on `job`. The old workers wind down in
+// the background, so this returns at once even while paused
+fn set_job(daemon: &Arc<Mutex<Daemon>>, job: Job) -> Value {
+    let mut guard = daemon.lock().unwrap();
+
+    guard.stop.store(true, Ordering::Relaxed);
+    guard.generation += 1;
+
+    let generation = guard.generation;
+    let stop = Arc::new(AtomicBool::new(false));
+    let previous = guard.search.take();
+    let miner = guard.miner.clone();
+
+    let miner_key = strkey::encode(strkey::ACCOUNT, &job.miner);
+    info!(job = job.index, miner = %miner_key, "Job: block {}, difficulty {}", job.index, job.difficulty);
+    miner.stats.begin_job(job.index, job.difficulty);
+    crate::logging::set_job(job.index, &miner_key);
+
+    guard.job = Some(job.clone());
+    guard.state = State::Mining;
+    guard.solution = None;
+    guard.started = Instant::now();
+    guard.base_hashes = miner.stats.snapshot().total();
+    guard.stop = stop.clone();
+
+    let search = thread::spawn({
+        let daemon = daemon.clone();
+
+        move || {
+            // Workers share their stats slots with the previous search
+            if let Some(previous) = previous {
+                let _ = previous.join();
+            }
+
+            let on_hit = |hit: crate::miner::Hit| {
+                if job.is_solution(hit.zeros) {
+                    solved(&daemon, generation, &job, hit.nonce, hit.hash);
+                }
+            };
+
+            let outcome = miner.search(&job, NonceRange::FULL, job.difficulty, &on_hit, &stop);
+            let mut daemon = daemon.lock().unwrap();
+
+            if matches!(outcome, Outcome::Exhausted) && daemon.generation == generation {
+                daemon.state = State::Exhausted;
+            }
+        }
+    });
+
+    guard.search = Some(search);
+
+    status(&guard)
+}
+
+fn solved(daemon: &Mutex<Daemon>, generation: u64, job: &Job, nonce: u64, hash: [u8; 32]) {
+    let mut daemon = daemon.lock().unwrap();
+
+    // Only the first solution of the current job counts
+    if daemon.generation != generation || daemon.state != State::Mining {
+        return;
+    }
+
+    daemon.stop.store(true, Ordering::Relaxed);
+    daemon.state = State::Solved;
+    daemon.miner.stats.record_solution();
+
+    job.print_solution(nonce, &hash);
+    info!(
+        job = job.index,
+        miner = %strkey::encode(strkey::ACCOUNT, &job.miner),
+        nonce,
+        "Solved block {}",
+        job.index
+    );
+
+    daemon.solution = Some(json!({
+        "nonce": nonce,
+        "hash": hex::encode(hash),
+        "message": job.message,
+    }));
+
+    if let Some(path) = &daemon.history {
+        let seconds = daemon.started.elapsed().as_secs_f64();
+        let miner = &daemon.miner;
+        let hashes = miner.stats.snapshot().total() - daemon.base_hashes;
+        let mut solve = Solve::new(job, nonce, hash, seconds, hashes, miner.threads);
+        solve.engine = miner.engine.name().to_string();
+        solve.order = miner.order;
+
+        if let Err(err) = history::append_solve(path, solve) {
+            error!(
+                job = job.index,
+                miner = %strkey::encode(strkey::ACCOUNT, &job.miner),
+                "Failed to write {}: {}",
+                path.display(),
+                err
+            );
+        }
+    }
+}
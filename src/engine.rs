@@ -0,0 +1,109 @@
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use tiny_keccak::{Hasher, Keccak};
+
+use crate::job::{count_leading_hex_zeros, Job};
+use crate::strkey;
+
+/// Keccak-256 implementation the workers hash with.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum Engine {
+    #[default]
+    TinyKeccak,
+    #[cfg(feature = "sha3")]
+    Sha3,
+}
+
+impl Engine {
+    /// Every engine compiled into this build.
+    pub const ALL: &'static [Engine] = &[
+        Engine::TinyKeccak,
+        #[cfg(feature = "sha3")]
+        Engine::Sha3,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Engine::TinyKeccak => "tiny-keccak",
+            #[cfg(feature = "sha3")]
+            Engine::Sha3 => "sha3",
+        }
+    }
+
+    #[inline(always)]
+    pub fn keccak256(&self, input: &[u8], out: &mut [u8; 32]) {
+        match self {
+            Engine::TinyKeccak => {
+                let mut keccak = Keccak::v256();
+                keccak.update(input);
+                keccak.finalize(out);
+            }
+            #[cfg(feature = "sha3")]
+            Engine::Sha3 => {
+                use sha3::Digest;
+
+                out.copy_from_slice(&sha3::Keccak256::digest(input));
+            }
+        }
+    }
+}
+
+// (index, prev_hash, message, nonce, expected hash), all for the same miner.
+// The first is independent of `Job::preimage`: its preimage was built from
+// stellar-xdr 21.2 `ScVal` encodings and hashed with RustCrypto's Keccak-256,
+// for block 1360 with the prev_hash hard-coded in the original `2_main.rs`. (The
+// HASH/NONCE pair in run.sh cannot be used, as its block's prev_hash was never
+// recorded.) The others came from `Job::preimage` and cover XDR padding of an
+// odd-length message and an empty one
+const KNOWN_ANSWERS: &[(u64, &str, &str, u64, &str)] = &[
+    (
+        1360,
+        "00000000f74c12d983233e69f7b7f2b0906c7d6757ea3d81cd17a69596aa38a5",
+        "KALE",
+        2322134,
+        "000008baeafd4ffe17d13a8637277dad5b4aa65ce76182e50bb8049d28654b11",
+    ),
+    (1, KNOWN_PREV_HASH, "KALE", 28733, "0000108c400b2803bc60a232835b376e4d11003a42fd51e39224d497257d1541"),
+    (1, KNOWN_PREV_HASH, "it's x", 251917, "00007426c247b817504245640793d3de7d3d20f0beff6fc74360bbb5f45af853"),
+    (7, KNOWN_PREV_HASH, "", 0, "fcd76a2ee4e0f43bdef2999b6ba9d0ad1cd717128485a1e717545d5d9c8b291a"),
+];
+const KNOWN_PREV_HASH: &str = "00000000ba94a25be3e2d0cdb1ef390342efbf2913f9ebf362a5cc98efe37ddf";
+
+/// Hash fixed preimages with every engine and compare against known answers, so
+/// a miscompiled or wrong-host build refuses to mine instead of wasting work.
+pub fn self_test() -> Result<(), String> {
+    let miner = strkey::decode(strkey::ACCOUNT, crate::MINER)?;
+
+    for &engine in Engine::ALL {
+        for &(index, prev_hash, message, nonce, expected) in KNOWN_ANSWERS {
+            let job = Job {
+                index,
+                prev_hash: hex::decode(prev_hash).unwrap().try_into().unwrap(),
+                difficulty: 0,
+                miner,
+                message: message.to_string(),
+            };
+
+            let mut hash = [0; 32];
+            job.preimage(engine).hash(nonce, &mut hash);
+
+            if hex::encode(hash) != expected {
+                return Err(format!(
+                    "{} hashed block {} nonce {} to {}, expected {}",
+                    engine.name(),
+                    index,
+                    nonce,
+                    hex::encode(hash),
+                    expected
+                ));
+            }
+        }
+    }
+
+    if count_leading_hex_zeros(&hex::decode(KNOWN_ANSWERS[0].4).unwrap()) != 5 {
+        return Err(String::from("leading zero count is wrong"));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,118 @@
+//! Failures caused by input or the environment rather than by bugs, each with
+//! the process exit code the CLI ends with.
+//!
+//! | code | meaning |
+//! |------|---------|
+//! | 0 | solved, or the command succeeded |
+//! | 1 | invalid input: hex, length, strkey, difficulty, nonce range, job, config |
+//! | 2 | invalid flags or arguments (reported by clap) |
+//! | 3 | reading or writing a file or socket failed |
+//! | 4 | an RPC request failed |
+//! | 5 | the hashing self-test failed |
+//! | 6 | the nonce range was exhausted without a solution |
+
+use serde_json::{json, Value};
+use std::fmt;
+use std::io;
+use std::path::Path;
+
+use crate::job::MAX_DIFFICULTY;
+
+/// Exit code of a search that used up its nonce range without a solution.
+pub const EXIT_EXHAUSTED: i32 = 6;
+
+#[derive(Debug)]
+pub enum MinerError {
+    InvalidHex { field: &'static str, reason: String },
+    WrongLength { field: &'static str, expected: usize, actual: usize },
+    InvalidStrkey { field: &'static str, reason: String },
+    Difficulty(usize),
+    NonceRange { start: u64, end: u64 },
+    /// A job that does not parse, e.g. from the pool's stdin or the daemon API.
+    InvalidJob(String),
+    NotFound(String),
+    /// A config file that does not parse or lacks the requested profile.
+    Config(String),
+    Io { target: String, source: io::Error },
+    Rpc(String),
+    /// A [`Replay`](crate::rpc::Replay) has no recorded response left for this method.
+    ReplayEnded(String),
+    SelfTest(String),
+}
+
+impl MinerError {
+    pub fn io(path: &Path, source: io::Error) -> Self {
+        MinerError::Io {
+            target: path.display().to_string(),
+            source,
+        }
+    }
+
+    /// Stable name for the `error` field of JSON events.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            MinerError::InvalidHex { .. } => "invalid_hex",
+            MinerError::WrongLength { .. } => "wrong_length",
+            MinerError::InvalidStrkey { .. } => "invalid_strkey",
+            MinerError::Difficulty(_) => "difficulty_out_of_range",
+            MinerError::NonceRange { .. } => "invalid_nonce_range",
+            MinerError::InvalidJob(_) => "invalid_job",
+            MinerError::NotFound(_) => "not_found",
+            MinerError::Config(_) => "config",
+            MinerError::Io { .. } => "io",
+            MinerError::Rpc(_) => "rpc",
+            MinerError::ReplayEnded(_) => "replay_ended",
+            MinerError::SelfTest(_) => "self_test",
+        }
+    }
+
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            MinerError::Io { .. } => 3,
+            MinerError::Rpc(_) => 4,
+            MinerError::SelfTest(_) => 5,
+            _ => 1,
+        }
+    }
+
+    /// `{"error": kind, "message": ..., "exit_code": ...}` for wrappers reading stdout.
+    pub fn to_event(&self) -> Value {
+        json!({
+            "error": self.kind(),
+            "message": self.to_string(),
+            "exit_code": self.exit_code(),
+        })
+    }
+}
+
+impl fmt::Display for MinerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MinerError::InvalidHex { field, reason } => write!(f, "{} is not valid hex: {}", field, reason),
+            MinerError::WrongLength { field, expected, actual } => {
+                write!(f, "{} must be {} bytes, got {}", field, expected, actual)
+            }
+            MinerError::InvalidStrkey { field, reason } => write!(f, "{} is not a valid key: {}", field, reason),
+            MinerError::Difficulty(zeros) => {
+                write!(f, "difficulty {} is out of range, at most {} zeros", zeros, MAX_DIFFICULTY)
+            }
+            MinerError::NonceRange { start, end } => write!(f, "--nonce-start {} is past --nonce-end {}", start, end),
+            MinerError::InvalidJob(reason) => write!(f, "invalid job: {}", reason),
+            MinerError::NotFound(what) => f.write_str(what),
+            MinerError::Config(reason) => write!(f, "invalid config: {}", reason),
+            MinerError::Io { target, source } => write!(f, "{}: {}", target, source),
+            MinerError::Rpc(reason) => write!(f, "RPC request failed: {}", reason),
+            MinerError::ReplayEnded(method) => write!(f, "no recorded response left for {}", method),
+            MinerError::SelfTest(reason) => write!(f, "self-test failed, refusing to mine: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for MinerError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            MinerError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
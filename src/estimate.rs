@@ -0,0 +1,153 @@
+use std::collections::HashSet;
+
+use crate::history::Solve;
+use crate::miner::Order;
+
+/// Chance that a single hash solves a block of `zeros` difficulty.
+///
+/// Workers accept exactly `zeros` leading hex zeros, so the next nibble must
+/// be non-zero as well.
+pub fn hit_probability(zeros: usize) -> f64 {
+    15.0 / 16f64.powi(zeros as i32 + 1)
+}
+
+pub fn expected_hashes(zeros: usize) -> f64 {
+    1.0 / hit_probability(zeros)
+}
+
+/// Expected seconds until a solution at `hashrate` H/s. Hashing is memoryless,
+/// so this does not shrink as time passes.
+pub fn eta(zeros: usize, hashrate: f64) -> f64 {
+    expected_hashes(zeros) / hashrate
+}
+
+/// Probability of finding a solution within `secs` seconds at `hashrate` H/s.
+pub fn solve_chance(zeros: usize, hashrate: f64, secs: f64) -> f64 {
+    -(-hashrate * secs * hit_probability(zeros)).exp_m1()
+}
+
+/// Hashes actually tried relative to the expected amount; above 1 is unlucky.
+pub fn luck(zeros: usize, hashes: u64) -> f64 {
+    hashes as f64 / expected_hashes(zeros)
+}
+
+pub fn format_duration(secs: f64) -> String {
+    if !secs.is_finite() {
+        return String::from("never");
+    }
+
+    let secs = secs.round() as u64;
+
+    match secs {
+        0..=59 => format!("{}s", secs),
+        60..=3599 => format!("{}m{:02}s", secs / 60, secs % 60),
+        3600..=86399 => format!("{}h{:02}m", secs / 3600, secs % 3600 / 60),
+        _ => format!("{}d{:02}h", secs / 86400, secs % 86400 / 3600),
+    }
+}
+
+// A solve is suspicious when workers counted this many times more hashes than
+// distinct nonces they could have covered
+const DUPLICATE_FACTOR: u64 = 2;
+// Per-thread allowance for batches counted past the winning nonce
+const DUPLICATE_SLACK: u64 = 1_000_000;
+
+// Pooled luck above this over enough solves points at wasted work
+const LUCK_ANOMALY: f64 = 2.0;
+const LUCK_MIN_SOLVES: usize = 5;
+
+/// Effective hashrate and anomalies over a history of solves.
+pub struct Summary<'a> {
+    solves: &'a [Solve],
+}
+
+impl<'a> Summary<'a> {
+    pub fn new(solves: &'a [Solve]) -> Self {
+        Self { solves }
+    }
+
+    pub fn anomalies(&self) -> Vec<String> {
+        let mut anomalies = vec![];
+        let mut seen = HashSet::new();
+
+        for solve in self.solves {
+            let covered = solve.nonce.saturating_sub(solve.nonce_start).saturating_add(1);
+            let slack = solve.threads as u64 * DUPLICATE_SLACK;
+
+            // Only an upward search ties the nonce to the work done
+            let ascending = solve.order == Order::Asc;
+
+            if ascending && solve.hashes > covered.saturating_mul(DUPLICATE_FACTOR).saturating_add(slack) {
+                anomalies.push(format!(
+                    "block {}: {} hashes counted but nonce is only {}, workers are repeating nonces",
+                    solve.index, solve.hashes, solve.nonce
+                ));
+            }
+
+            if !seen.insert((solve.index, &solve.prev_hash, solve.nonce)) {
+                anomalies.push(format!(
+                    "block {}: nonce {} recorded more than once",
+                    solve.index, solve.nonce
+                ));
+            }
+        }
+
+        let luck = self.hashes() as f64 / self.expected();
+
+        if self.solves.len() >= LUCK_MIN_SOLVES && luck > LUCK_ANOMALY {
+            anomalies.push(format!(
+                "average luck {:.2}: effective hashrate is {:.1}x below measured",
+                luck, luck
+            ));
+        }
+
+        anomalies
+    }
+
+    fn seconds(&self) -> f64 {
+        self.solves.iter().map(|solve| solve.seconds).sum()
+    }
+
+    fn hashes(&self) -> u64 {
+        self.solves.iter().map(|solve| solve.hashes).sum()
+    }
+
+    fn expected(&self) -> f64 {
+        self.solves
+            .iter()
+            .map(|solve| expected_hashes(solve.difficulty))
+            .sum()
+    }
+}
+
+impl std::fmt::Display for Summary<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let seconds = self.seconds();
+        let hashes = self.hashes();
+        let expected = self.expected();
+
+        writeln!(f, "Solves: {}", self.solves.len())?;
+
+        if self.solves.is_empty() {
+            return Ok(());
+        }
+
+        writeln!(f, "Time mining: {}", format_duration(seconds))?;
+        writeln!(f, "Hashes: {} ({:.0} expected)", hashes, expected)?;
+        writeln!(f, "Measured hashrate: {:.2} MH/s", hashes as f64 / seconds / 1_000_000.0)?;
+        writeln!(f, "Effective hashrate: {:.2} MH/s", expected / seconds / 1_000_000.0)?;
+        writeln!(f, "Luck: {:.2}", hashes as f64 / expected)?;
+
+        let lucks = self.solves.iter().map(|solve| luck(solve.difficulty, solve.hashes));
+        let best = lucks.clone().fold(f64::INFINITY, f64::min);
+        let worst = lucks.fold(0.0, f64::max);
+
+        writeln!(f, "Best luck: {:.2}, worst luck: {:.2}", best, worst)?;
+
+        for anomaly in self.anomalies() {
+            writeln!(f, "Anomaly: {}", anomaly)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,237 @@
+//! C ABI for driving the miner in-process, e.g. from Bun FFI.
+//!
+//! Ownership: every `*_new`/`*_start` result belongs to the caller and must be
+//! released with the matching `*_free` exactly once. Strings and buffers passed
+//! in are copied, so they may be freed as soon as the call returns.
+//!
+//! Threads: `fcm_cancel` may be called from any thread at any time. Other
+//! functions may be called from any thread, but not concurrently on the same
+//! handle with `*_free`.
+//!
+//! The header is `include/fcm_miner.h`, regenerated with `make header`.
+
+use std::ffi::{c_char, CStr};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::Mutex;
+
+use crate::job::{count_leading_hex_zeros, Job, MAX_DIFFICULTY};
+use crate::miner::NonceRange;
+use crate::session::{MineOptions, MinerEvent, Session};
+use crate::strkey;
+
+pub const FCM_EVENT_HASHRATE: u32 = 1;
+pub const FCM_EVENT_BEST: u32 = 2;
+pub const FCM_EVENT_SOLUTION: u32 = 3;
+pub const FCM_EVENT_EXHAUSTED: u32 = 4;
+
+/// Opaque block template.
+pub struct FcmJob(Job);
+
+/// Opaque running search.
+pub struct FcmMiner {
+    session: Session,
+    events: Mutex<Receiver<MinerEvent>>,
+}
+
+/// One event, filled in by `fcm_poll_event`. Fields a kind does not use are zero.
+#[repr(C)]
+pub struct FcmEvent {
+    /// One of the `FCM_EVENT_*` constants.
+    pub kind: u32,
+    pub zeros: u32,
+    pub nonce: u64,
+    pub hashes: u64,
+    /// H/s since the previous hashrate event.
+    pub rate: f64,
+    pub hash: [u8; 32],
+}
+
+/// A job from a 32-byte `prev_hash`, a `G...` miner address and a NUL-terminated
+/// UTF-8 message. Returns NULL if any argument is invalid.
+///
+/// # Safety
+/// `prev_hash` must point to 32 readable bytes; `miner` and `message` must be
+/// NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn fcm_job_new(
+    index: u64,
+    prev_hash: *const u8,
+    difficulty: u32,
+    miner: *const c_char,
+    message: *const c_char,
+) -> *mut FcmJob {
+    if prev_hash.is_null() || miner.is_null() || message.is_null() || difficulty as usize > MAX_DIFFICULTY {
+        return std::ptr::null_mut();
+    }
+
+    let prev_hash = std::slice::from_raw_parts(prev_hash, 32).try_into().unwrap();
+
+    let (Ok(miner), Ok(message)) = (CStr::from_ptr(miner).to_str(), CStr::from_ptr(message).to_str()) else {
+        return std::ptr::null_mut();
+    };
+
+    let Ok(miner) = strkey::decode(strkey::ACCOUNT, miner) else {
+        return std::ptr::null_mut();
+    };
+
+    Box::into_raw(Box::new(FcmJob(Job {
+        index,
+        prev_hash,
+        difficulty: difficulty as usize,
+        miner,
+        message: message.to_string(),
+    })))
+}
+
+/// # Safety
+/// `job` must come from `fcm_job_new` and not be used afterwards. NULL is ignored.
+#[no_mangle]
+pub unsafe extern "C" fn fcm_job_free(job: *mut FcmJob) {
+    if !job.is_null() {
+        drop(Box::from_raw(job));
+    }
+}
+
+/// Start searching `nonce_start..=nonce_end` on `threads` workers (0 = all
+/// CPUs). The job is copied. Returns NULL if the range is empty, the job is
+/// NULL or the hashing self-test fails.
+///
+/// # Safety
+/// `job` must be NULL or come from `fcm_job_new`.
+#[no_mangle]
+pub unsafe extern "C" fn fcm_mine_start(
+    job: *const FcmJob,
+    threads: u32,
+    nonce_start: u64,
+    nonce_end: u64,
+) -> *mut FcmMiner {
+    if job.is_null() || nonce_start > nonce_end {
+        return std::ptr::null_mut();
+    }
+
+    let opts = MineOptions {
+        threads: (threads > 0).then_some(threads as usize),
+        range: NonceRange {
+            start: nonce_start,
+            last: nonce_end,
+        },
+        ..MineOptions::default()
+    };
+
+    let (tx, rx) = mpsc::channel();
+
+    let session = Session::start((*job).0.clone(), opts, move |event| {
+        let _ = tx.send(event);
+    });
+
+    match session {
+        Ok(session) => Box::into_raw(Box::new(FcmMiner {
+            session,
+            events: Mutex::new(rx),
+        })),
+        Err(_) => std::ptr::null_mut(),
+    }
+}
+
+/// Take the next event without blocking. Returns 1 when `out` was filled, 0
+/// when nothing is pending yet and -1 once the search is over and drained.
+///
+/// # Safety
+/// `miner` must come from `fcm_mine_start`; `out` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn fcm_poll_event(miner: *mut FcmMiner, out: *mut FcmEvent) -> i32 {
+    if miner.is_null() || out.is_null() {
+        return -1;
+    }
+
+    let event = match (*miner).events.lock().unwrap().try_recv() {
+        Ok(event) => event,
+        Err(TryRecvError::Empty) => return 0,
+        Err(TryRecvError::Disconnected) => return -1,
+    };
+
+    let mut result = FcmEvent {
+        kind: 0,
+        zeros: 0,
+        nonce: 0,
+        hashes: 0,
+        rate: 0.0,
+        hash: [0; 32],
+    };
+
+    match event {
+        MinerEvent::Hashrate { hashes, rate } => {
+            result.kind = FCM_EVENT_HASHRATE;
+            result.hashes = hashes;
+            result.rate = rate;
+        }
+        MinerEvent::Best { nonce, hash, zeros } => {
+            result.kind = FCM_EVENT_BEST;
+            result.nonce = nonce;
+            result.hash = hash;
+            result.zeros = zeros as u32;
+        }
+        MinerEvent::Solution(solution) => {
+            result.kind = FCM_EVENT_SOLUTION;
+            result.nonce = solution.nonce;
+            result.hash = solution.hash;
+            result.zeros = solution.zeros as u32;
+            result.hashes = solution.hashes;
+        }
+        MinerEvent::Exhausted { hashes } => {
+            result.kind = FCM_EVENT_EXHAUSTED;
+            result.hashes = hashes;
+        }
+    }
+
+    out.write(result);
+
+    1
+}
+
+/// Ask the workers to stop after their current batch; events already queued
+/// can still be polled.
+///
+/// # Safety
+/// `miner` must be NULL or come from `fcm_mine_start`.
+#[no_mangle]
+pub unsafe extern "C" fn fcm_cancel(miner: *const FcmMiner) {
+    if !miner.is_null() {
+        (*miner).session.cancel();
+    }
+}
+
+/// Cancel the search if it is still running and release the handle. The
+/// workers wind down in the background.
+///
+/// # Safety
+/// `miner` must come from `fcm_mine_start` and not be used afterwards. NULL is ignored.
+#[no_mangle]
+pub unsafe extern "C" fn fcm_miner_free(miner: *mut FcmMiner) {
+    if !miner.is_null() {
+        let miner = Box::from_raw(miner);
+        miner.session.cancel();
+    }
+}
+
+/// Hash `nonce` for `job` into `out_hash` (if not NULL). Returns 1 if it solves
+/// the job, 0 if not and -1 if `job` is NULL.
+///
+/// # Safety
+/// `job` must be NULL or come from `fcm_job_new`; `out_hash` must be NULL or
+/// point to 32 writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn fcm_verify(job: *const FcmJob, nonce: u64, out_hash: *mut u8) -> i32 {
+    if job.is_null() {
+        return -1;
+    }
+
+    let job = &(*job).0;
+    let hash = job.hash(nonce);
+
+    if !out_hash.is_null() {
+        std::ptr::copy_nonoverlapping(hash.as_ptr(), out_hash, 32);
+    }
+
+    job.is_solution(count_leading_hex_zeros(&hash)) as i32
+}
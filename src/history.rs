@@ -0,0 +1,380 @@
+use clap::{Subcommand, ValueEnum};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::info;
+
+use crate::engine::Engine;
+use crate::error::MinerError;
+use crate::job::{count_leading_hex_zeros, Job};
+use crate::miner::Order;
+use crate::strkey;
+
+/// One solved block.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Solve {
+    pub time: u64,
+    pub index: u64,
+    pub prev_hash: String,
+    pub message: String,
+    pub miner: String,
+    pub difficulty: usize,
+    pub nonce: u64,
+    pub hash: String,
+    pub zeros: usize,
+    pub seconds: f64,
+    pub hashes: u64,
+    pub threads: usize,
+    pub engine: String,
+    pub host: String,
+    // Solves recorded before orders existed all searched upwards
+    #[serde(default)]
+    pub order: Order,
+    #[serde(default)]
+    pub nonce_start: u64,
+    #[serde(default)]
+    pub status: Status,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tx_hash: Option<String>,
+}
+
+impl Solve {
+    pub fn new(job: &Job, nonce: u64, hash: [u8; 32], seconds: f64, hashes: u64, threads: usize) -> Self {
+        Self {
+            time: now(),
+            index: job.index,
+            prev_hash: hex::encode(job.prev_hash),
+            message: job.message.clone(),
+            miner: strkey::encode(strkey::ACCOUNT, &job.miner),
+            difficulty: job.difficulty,
+            nonce,
+            hash: hex::encode(hash),
+            zeros: count_leading_hex_zeros(&hash),
+            seconds,
+            hashes,
+            threads,
+            engine: Engine::default().name().to_string(),
+            host: hostname(),
+            order: Order::Asc,
+            nonce_start: 0,
+            status: Status::Pending,
+            tx_hash: None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq, ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    #[default]
+    Pending,
+    Submitted,
+    Confirmed,
+    Failed,
+}
+
+impl std::fmt::Display for Status {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Status::Pending => "pending",
+            Status::Submitted => "submitted",
+            Status::Confirmed => "confirmed",
+            Status::Failed => "failed",
+        })
+    }
+}
+
+/// Submission outcome for an earlier solve, reported by whoever sent it.
+#[derive(Serialize, Deserialize)]
+pub struct Submission {
+    pub time: u64,
+    pub index: u64,
+    pub nonce: u64,
+    pub status: Status,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tx_hash: Option<String>,
+}
+
+// The ledger is append-only JSON lines; submissions are folded into their
+// solve when it is read back
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Entry {
+    Solve(Solve),
+    Submission(Submission),
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// List recorded solves, newest last
+    List {
+        /// Only show the last N solves
+        #[arg(short = 'n', long)]
+        limit: Option<usize>,
+    },
+    /// Show every solve recorded for a block
+    Show {
+        /// Block index
+        index: u64,
+
+        /// Only the solve with this nonce
+        #[arg(long)]
+        nonce: Option<u64>,
+    },
+    /// Export the ledger for reconciliation
+    Export {
+        #[arg(long, value_enum, default_value_t = Format::Csv)]
+        format: Format,
+
+        /// Write to a file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Record the submission outcome of a solve
+    Submit {
+        /// Block index
+        #[arg(long)]
+        index: u64,
+
+        #[arg(long)]
+        nonce: u64,
+
+        #[arg(long, value_enum)]
+        status: Status,
+
+        /// Transaction hash, when known
+        #[arg(long)]
+        tx_hash: Option<String>,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Format {
+    Csv,
+    Json,
+}
+
+/// `$XDG_DATA_HOME/fcm-miner/history.jsonl`, falling back to `~/.local/share`.
+pub fn default_path() -> PathBuf {
+    let data_home = std::env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".local/share")))
+        .unwrap_or_default();
+
+    data_home.join("fcm-miner").join("history.jsonl")
+}
+
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+pub fn hostname() -> String {
+    fs::read_to_string("/proc/sys/kernel/hostname")
+        .ok()
+        .or_else(|| std::env::var("HOSTNAME").ok())
+        .map(|host| host.trim().to_string())
+        .filter(|host| !host.is_empty())
+        .unwrap_or_else(|| String::from("unknown"))
+}
+
+pub fn append_solve(path: &Path, solve: Solve) -> io::Result<()> {
+    append(path, &Entry::Solve(solve))
+}
+
+pub fn append_submission(path: &Path, submission: Submission) -> io::Result<()> {
+    append(path, &Entry::Submission(submission))
+}
+
+/// Append one JSON line to `path`, creating it and its directory if needed.
+pub fn append<T: Serialize>(path: &Path, entry: &T) -> io::Result<()> {
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)?;
+    }
+
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    let line = serde_json::to_string(entry)?;
+
+    // One write per entry so concurrent miners never interleave lines
+    file.write_all(format!("{}\n", line).as_bytes())
+}
+
+pub fn load(path: &Path) -> io::Result<Vec<Solve>> {
+    let reader = match File::open(path) {
+        Ok(file) => BufReader::new(file),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(err) => return Err(err),
+    };
+
+    let mut solves: Vec<Solve> = vec![];
+
+    for (number, line) in reader.lines().enumerate() {
+        let line = line?;
+
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let entry = serde_json::from_str(&line).map_err(|err| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}:{}: {}", path.display(), number + 1, err),
+            )
+        })?;
+
+        match entry {
+            Entry::Solve(solve) => solves.push(solve),
+            Entry::Submission(submission) => {
+                let solve = solves
+                    .iter_mut()
+                    .rev()
+                    .find(|solve| solve.index == submission.index && solve.nonce == submission.nonce);
+
+                if let Some(solve) = solve {
+                    solve.status = submission.status;
+                    solve.tx_hash = submission.tx_hash.or(solve.tx_hash.take());
+                }
+            }
+        }
+    }
+
+    Ok(solves)
+}
+
+pub fn write_csv(out: &mut impl Write, solves: &[Solve]) -> io::Result<()> {
+    writeln!(
+        out,
+        "time,index,prev_hash,message,miner,difficulty,nonce,hash,zeros,seconds,hashes,threads,engine,host,status,tx_hash"
+    )?;
+
+    for solve in solves {
+        writeln!(
+            out,
+            "{},{},{},{},{},{},{},{},{},{:.3},{},{},{},{},{},{}",
+            solve.time,
+            solve.index,
+            solve.prev_hash,
+            csv_field(&solve.message),
+            solve.miner,
+            solve.difficulty,
+            solve.nonce,
+            solve.hash,
+            solve.zeros,
+            solve.seconds,
+            solve.hashes,
+            solve.threads,
+            csv_field(&solve.engine),
+            csv_field(&solve.host),
+            solve.status,
+            solve.tx_hash.as_deref().unwrap_or("")
+        )?;
+    }
+
+    Ok(())
+}
+
+pub fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// `output`, or stdout when there is none.
+pub fn create_output(output: Option<&Path>) -> Result<Box<dyn Write>, MinerError> {
+    match output {
+        Some(output) => match File::create(output) {
+            Ok(file) => Ok(Box::new(file)),
+            Err(err) => Err(MinerError::io(output, err)),
+        },
+        None => Ok(Box::new(io::stdout().lock())),
+    }
+}
+
+/// [`load`], with the path in the error.
+pub fn load_ledger(path: &Path) -> Result<Vec<Solve>, MinerError> {
+    load(path).map_err(|err| MinerError::io(path, err))
+}
+
+pub fn run(path: &Path, action: Command) -> Result<(), MinerError> {
+    match action {
+        Command::List { limit } => {
+            let solves = load_ledger(path)?;
+            let skip = limit.map_or(0, |limit| solves.len().saturating_sub(limit));
+
+            println!(
+                "{:>10} {:>8} {:>5} {:>5} {:>20} {:>9} {:>14} {:<16} status",
+                "time", "index", "diff", "zeros", "nonce", "seconds", "hashes", "host"
+            );
+
+            for solve in &solves[skip..] {
+                println!(
+                    "{:>10} {:>8} {:>5} {:>5} {:>20} {:>9.2} {:>14} {:<16} {}",
+                    solve.time,
+                    solve.index,
+                    solve.difficulty,
+                    solve.zeros,
+                    solve.nonce,
+                    solve.seconds,
+                    solve.hashes,
+                    solve.host,
+                    solve.status
+                );
+            }
+        }
+        Command::Show { index, nonce } => {
+            let solves: Vec<Solve> = load_ledger(path)?
+                .into_iter()
+                .filter(|solve| solve.index == index && nonce.is_none_or(|nonce| solve.nonce == nonce))
+                .collect();
+
+            if solves.is_empty() {
+                return Err(MinerError::NotFound(format!("no solves recorded for block {}", index)));
+            }
+
+            println!("{}", serde_json::to_string_pretty(&solves).unwrap());
+        }
+        Command::Export { format, output } => {
+            let solves = load_ledger(path)?;
+            let mut out = create_output(output.as_deref())?;
+
+            let result = match format {
+                Format::Csv => write_csv(&mut out, &solves),
+                Format::Json => serde_json::to_writer_pretty(&mut out, &solves)
+                    .map_err(io::Error::from)
+                    .and_then(|()| writeln!(out)),
+            };
+
+            result.map_err(|err| MinerError::Io {
+                target: String::from("export"),
+                source: err,
+            })?;
+        }
+        Command::Submit { index, nonce, status, tx_hash } => {
+            // The miner is only in the solve this outcome belongs to
+            let miner = load(path)
+                .ok()
+                .and_then(|solves| solves.into_iter().find(|solve| solve.index == index && solve.nonce == nonce))
+                .map(|solve| solve.miner)
+                .unwrap_or_default();
+
+            let submission = Submission {
+                time: now(),
+                index,
+                nonce,
+                status,
+                tx_hash,
+            };
+
+            append_submission(path, submission).map_err(|err| MinerError::io(path, err))?;
+
+            info!(job = index, miner = %miner, nonce, "Submission {}", status);
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,192 @@
+use serde::{Deserialize, Serialize};
+
+use crate::engine::Engine;
+use crate::error::MinerError;
+use crate::history;
+use crate::strkey;
+
+/// Most leading zeros `count_leading_hex_zeros` can report.
+pub const MAX_DIFFICULTY: usize = 32;
+
+/// Everything that goes into the preimage except the nonce.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct Job {
+    pub index: u64,
+    #[serde(with = "hash_hex")]
+    pub prev_hash: [u8; 32],
+    pub difficulty: usize,
+    #[serde(with = "strkey::account")]
+    pub miner: [u8; 32],
+    pub message: String,
+}
+
+impl Job {
+    /// Workers accept exactly `difficulty` leading zeros.
+    #[inline]
+    pub fn is_solution(&self, zeros: usize) -> bool {
+        zeros == self.difficulty
+    }
+
+    pub fn preimage(&self, engine: Engine) -> Preimage {
+        let message = self.message.as_bytes();
+        let padding = (4 - message.len() % 4) % 4;
+
+        let mut buffer = Vec::with_capacity(120 + message.len() + padding);
+
+        buffer.extend_from_slice(&[0, 0, 0, 5]);
+        buffer.extend_from_slice(&self.index.to_be_bytes());
+
+        buffer.extend_from_slice(&[0, 0, 0, 14]);
+        buffer.extend_from_slice(&(message.len() as u32).to_be_bytes());
+        buffer.extend_from_slice(message);
+        buffer.extend_from_slice(&[0; 3][..padding]);
+
+        buffer.extend_from_slice(&[0, 0, 0, 13, 0, 0, 0, 32]);
+        buffer.extend_from_slice(&self.prev_hash);
+
+        buffer.extend_from_slice(&[0, 0, 0, 5]);
+        let nonce_offset = buffer.len();
+        buffer.extend_from_slice(&[0; 8]);
+
+        buffer.extend_from_slice(&[0, 0, 0, 18, 0, 0, 0, 0, 0, 0, 0, 0]);
+        buffer.extend_from_slice(&self.miner);
+
+        Preimage {
+            buffer,
+            nonce_offset,
+            engine,
+        }
+    }
+
+    /// Hash with the reference engine, for verifying reported nonces.
+    pub fn hash(&self, nonce: u64) -> [u8; 32] {
+        let mut hash = [0; 32];
+        self.preimage(Engine::default()).hash(nonce, &mut hash);
+
+        hash
+    }
+
+    /// Print the solution line, `[nonce, "hash", "message"]`, to stdout. The
+    /// message goes with the nonce, since it may differ per host.
+    pub fn print_solution(&self, nonce: u64, hash: &[u8; 32]) {
+        println!("{}", serde_json::json!([nonce, hex::encode(hash), self.message]));
+    }
+}
+
+/// A 32-byte hash in hex, optionally `0x`-prefixed, in either case.
+pub fn parse_hash(field: &'static str, value: &str) -> Result<[u8; 32], MinerError> {
+    let value = value.trim();
+    let digits = value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+        .unwrap_or(value);
+
+    let bytes = hex::decode(digits).map_err(|err| MinerError::InvalidHex {
+        field,
+        reason: err.to_string(),
+    })?;
+
+    let actual = bytes.len();
+
+    bytes.try_into().map_err(|_| MinerError::WrongLength {
+        field,
+        expected: 32,
+        actual,
+    })
+}
+
+pub fn check_difficulty(difficulty: usize) -> Result<usize, MinerError> {
+    if difficulty > MAX_DIFFICULTY {
+        return Err(MinerError::Difficulty(difficulty));
+    }
+
+    Ok(difficulty)
+}
+
+/// A job from JSON, as sent to the pool and the daemon. Missing `miner` and
+/// `message` fields take the defaults, and the message template is filled in
+/// for this host and `worker` (the hostname if `None`).
+pub fn from_json(mut job: serde_json::Value, miner: &str, message: &str, worker: Option<&str>) -> Result<Job, MinerError> {
+    let Some(fields) = job.as_object_mut() else {
+        return Err(MinerError::InvalidJob(String::from("expected a JSON object")));
+    };
+
+    fields.entry("miner").or_insert_with(|| miner.into());
+    fields.entry("message").or_insert_with(|| message.into());
+
+    // Check the fields users get wrong first, so they get a specific error
+    if let Some(prev_hash) = fields.get("prev_hash").and_then(|value| value.as_str()) {
+        parse_hash("prev_hash", prev_hash)?;
+    }
+
+    if let Some(miner) = fields.get("miner").and_then(|value| value.as_str()) {
+        strkey::decode(strkey::ACCOUNT, miner)
+            .map_err(|reason| MinerError::InvalidStrkey { field: "miner", reason })?;
+    }
+
+    let mut job: Job = serde_json::from_value(job).map_err(|err| MinerError::InvalidJob(err.to_string()))?;
+    check_difficulty(job.difficulty)?;
+
+    let host = history::hostname();
+    job.message = expand_message(&job.message, &host, worker.unwrap_or(&host));
+
+    Ok(job)
+}
+
+/// Serde helpers for hashes, read with [`parse_hash`] and written as lowercase hex.
+pub mod hash_hex {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(hash: &[u8; 32], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&hex::encode(hash))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<[u8; 32], D::Error> {
+        let value = String::deserialize(deserializer)?;
+
+        super::parse_hash("prev_hash", &value).map_err(serde::de::Error::custom)
+    }
+}
+
+/// Fill in a message template. `{host}` and `{worker}` make the message, and
+/// with it the whole 2^64 nonce space, unique to each machine or worker.
+pub fn expand_message(template: &str, host: &str, worker: &str) -> String {
+    template.replace("{host}", host).replace("{worker}", worker)
+}
+
+/// XDR-encoded `(index, message, prev_hash, nonce, miner)` with a slot for the nonce.
+#[derive(Clone)]
+pub struct Preimage {
+    buffer: Vec<u8>,
+    nonce_offset: usize,
+    engine: Engine,
+}
+
+impl Preimage {
+    #[inline(always)]
+    pub fn hash(&mut self, nonce: u64, out: &mut [u8; 32]) {
+        self.buffer[self.nonce_offset..self.nonce_offset + 8].copy_from_slice(&nonce.to_be_bytes());
+
+        self.engine.keccak256(&self.buffer, out);
+    }
+}
+
+pub fn count_leading_hex_zeros(hash: &[u8]) -> usize {
+    // First 8 bytes
+    let first_u64 = u64::from_be_bytes([
+        hash[0], hash[1], hash[2], hash[3], hash[4], hash[5], hash[6], hash[7],
+    ]);
+
+    let leading_zeros = first_u64.leading_zeros();
+    let first_count = (leading_zeros as usize) / 4;
+
+    // If we used all first 8 bytes, check next 8
+    if first_count == 16 {
+        let second_u64 = u64::from_be_bytes([
+            hash[8], hash[9], hash[10], hash[11], hash[12], hash[13], hash[14], hash[15],
+        ]);
+        first_count + (second_u64.leading_zeros() as usize) / 4
+    } else {
+        first_count
+    }
+}
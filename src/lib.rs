@@ -0,0 +1,25 @@
+//! Keccak proof-of-work search for FCM blocks, shared by the CLI and embedders.
+
+pub mod engine;
+pub mod error;
+pub mod estimate;
+pub mod ffi;
+pub mod history;
+pub mod job;
+pub mod miner;
+pub mod network;
+pub mod rpc;
+pub mod session;
+pub mod stats;
+pub mod strkey;
+pub mod throttle;
+pub mod tune;
+pub mod xdr;
+
+#[cfg(feature = "async")]
+pub mod api;
+
+/// Message mined with when none is given.
+pub const MESSAGE: &str = "KALE";
+/// Miner address mined for when none is given.
+pub const MINER: &str = "GBDVX4VELCDSQ54KQJYTNHXAHFLBCA77ZY2USQBM4CSHTTV7DME7KALE";
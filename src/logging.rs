@@ -0,0 +1,161 @@
+//! Diagnostics go through `tracing` to stderr or a log file; stdout carries
+//! only the solution and event protocol. While the dashboard is up, stderr
+//! lines go to its log pane instead.
+
+use clap::{Args, ValueEnum};
+use std::io::{self, IsTerminal, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tracing::level_filters::LevelFilter;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
+
+type Sink = Box<dyn Fn(&str) + Send>;
+
+// While set, lines meant for stderr go here instead
+static DIVERT: Mutex<Option<Sink>> = Mutex::new(None);
+
+// Job being mined and its miner, for errors logged away from the job's own code
+static JOB: Mutex<Option<(u64, String)>> = Mutex::new(None);
+
+#[derive(Args)]
+pub struct LogArgs {
+    /// Most verbose level to log
+    #[arg(long, global = true, env = "FCM_LOG_LEVEL", value_enum, default_value_t = Level::Info)]
+    pub log_level: Level,
+
+    /// Human-readable lines or one JSON object per event
+    #[arg(long, global = true, env = "FCM_LOG_FORMAT", value_enum, default_value_t = LogFormat::Text)]
+    pub log_format: LogFormat,
+
+    /// Log to this file instead of stderr
+    #[arg(long, global = true, env = "FCM_LOG_FILE")]
+    pub log_file: Option<PathBuf>,
+
+    /// Start a new log file this often, suffixed with the date
+    #[arg(long, global = true, value_enum, default_value_t = LogRotation::Daily)]
+    pub log_rotation: LogRotation,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Level {
+    Off,
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum LogRotation {
+    Hourly,
+    Daily,
+    Never,
+}
+
+/// Install the global subscriber; call once, before anything logs.
+pub fn init(args: &LogArgs) {
+    let level = match args.log_level {
+        Level::Off => LevelFilter::OFF,
+        Level::Error => LevelFilter::ERROR,
+        Level::Warn => LevelFilter::WARN,
+        Level::Info => LevelFilter::INFO,
+        Level::Debug => LevelFilter::DEBUG,
+        Level::Trace => LevelFilter::TRACE,
+    };
+
+    let (writer, ansi) = match &args.log_file {
+        Some(path) => {
+            let rotation = match args.log_rotation {
+                LogRotation::Hourly => Rotation::HOURLY,
+                LogRotation::Daily => Rotation::DAILY,
+                LogRotation::Never => Rotation::NEVER,
+            };
+            let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty());
+            let name = path.file_name().unwrap_or(path.as_os_str());
+
+            // Writes are synchronous so nothing is lost when the miner exits
+            let appender = RollingFileAppender::new(rotation, dir.unwrap_or(".".as_ref()), name);
+
+            (BoxMakeWriter::new(appender), false)
+        }
+        None => (BoxMakeWriter::new(|| Stderr), io::stderr().is_terminal()),
+    };
+
+    let builder = tracing_subscriber::fmt()
+        .with_max_level(level)
+        .with_target(false)
+        .with_ansi(ansi)
+        .with_writer(writer);
+
+    match args.log_format {
+        LogFormat::Text => builder.init(),
+        LogFormat::Json => builder.json().init(),
+    }
+}
+
+/// Hand what would be logged to stderr to `sink` instead, one line at a time
+/// without colours, until [`restore`]. A log file is unaffected.
+pub fn divert(sink: impl Fn(&str) + Send + 'static) {
+    *DIVERT.lock().unwrap() = Some(Box::new(sink));
+}
+
+pub fn restore() {
+    *DIVERT.lock().unwrap() = None;
+}
+
+/// Tag errors reported from now on with `job` and `miner`, the same fields the
+/// job's own log lines carry.
+pub fn set_job(job: u64, miner: &str) {
+    *JOB.lock().unwrap() = Some((job, miner.to_string()));
+}
+
+/// The job and miner last passed to [`set_job`].
+pub fn job() -> Option<(u64, String)> {
+    JOB.lock().unwrap().clone()
+}
+
+// Stderr unless diverted. Each event arrives formatted in a single write
+struct Stderr;
+
+impl Write for Stderr {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match DIVERT.lock().unwrap().as_ref() {
+            Some(sink) => {
+                for line in String::from_utf8_lossy(buf).lines() {
+                    sink(&strip_ansi(line));
+                }
+
+                Ok(buf.len())
+            }
+            None => io::stderr().write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        io::stderr().flush()
+    }
+}
+
+fn strip_ansi(line: &str) -> String {
+    let mut plain = String::with_capacity(line.len());
+    let mut chars = line.chars();
+
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // Skip to the end of the escape sequence, a letter
+            chars.by_ref().find(char::is_ascii_alphabetic);
+        } else {
+            plain.push(c);
+        }
+    }
+
+    plain
+}
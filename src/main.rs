@@ -1,179 +1,630 @@
-use clap::Parser;
-use std::sync::atomic::{AtomicU64, Ordering};
-use std::sync::Arc;
+use clap::builder::PossibleValue;
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+use std::ffi::OsString;
+use std::io::{BufRead, IsTerminal};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
 use std::thread;
-use tiny_keccak::{Hasher, Keccak};
-use std::time::{Duration, Instant};
-
-const BATCH_SIZE: usize = 50_000;
-const BATCH_SIZE_U64: u64 = BATCH_SIZE as u64;
+use std::time::Instant;
+use tracing::{error, info, warn};
+
+mod config;
+mod daemon;
+mod logging;
+#[cfg(feature = "metrics")]
+mod metrics;
+mod pool;
+mod tui;
+mod watch;
+
+use fcm_miner_rust::{
+    engine, error, estimate, history, job, miner, network, rpc, stats, strkey, throttle, tune, MESSAGE, MINER,
+};
+
+use engine::Engine;
+use error::MinerError;
+use history::Solve;
+use job::{expand_message, Job};
+use miner::{Miner, NonceRange, Order, Outcome};
+use stats::Stats;
+use throttle::Throttle;
 
 #[derive(Parser)]
-#[command(author, version, about, long_about = None)]
+#[command(author, version, about, long_about = None, subcommand_negates_reqs = true)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Block index
-    #[arg(short, long)]
-    index: u64,
+    #[arg(short, long, required = true)]
+    index: Option<u64>,
 
-    /// Previous block hash (hex string)
-    #[arg(short, long)]
-    prev_hash: String,
+    /// Previous block hash (hex string, optionally 0x-prefixed)
+    #[arg(short, long, required = true)]
+    prev_hash: Option<String>,
 
     /// Number of leading zeros required
-    #[arg(short, long)]
-    target_zeros: usize,
-}
+    #[arg(short, long, required = true)]
+    target_zeros: Option<usize>,
+
+    /// Miner address
+    #[arg(long, env = "FCM_MINER", default_value = MINER)]
+    miner: String,
+
+    /// Message included in the preimage; `{host}` and `{worker}` are filled in
+    #[arg(long, env = "FCM_MESSAGE", default_value = MESSAGE)]
+    message: String,
+
+    /// Name substituted for `{worker}` in the message (defaults to the hostname)
+    #[arg(long, env = "FCM_WORKER")]
+    worker: Option<String>,
+
+    #[command(flatten)]
+    miner_args: MinerArgs,
+
+    /// First nonce to try
+    #[arg(long, default_value_t = 0)]
+    nonce_start: u64,
+
+    /// Last nonce to try (inclusive)
+    #[arg(long, default_value_t = u64::MAX)]
+    nonce_end: u64,
+
+    /// Search upwards on one thread so the lowest qualifying nonce always wins, without tuning
+    #[arg(long, conflicts_with_all = ["threads", "order", "seed"])]
+    deterministic: bool,
 
-fn build_prefix(index: u64, prev_hash: [u8; 32]) -> [u8; 68] {
-    let message = "KALE";
+    /// Keep mining and stream every hash with at least this many zeros
+    #[arg(long)]
+    share_zeros: Option<usize>,
 
-    let mut prefix = [0; 68];
+    /// Zero counts to tag streamed hashes with (defaults to share..=target)
+    #[arg(long, value_delimiter = ',', requires = "share_zeros")]
+    thresholds: Vec<usize>,
 
-    prefix[..4].copy_from_slice(&[0, 0, 0, 5]);
-    prefix[4..12].copy_from_slice(&index.to_be_bytes());
+    /// Live dashboard instead of scrolling hashrate lines (when stdout is a terminal)
+    #[arg(long)]
+    tui: bool,
 
-    prefix[12..20].copy_from_slice(&[0, 0, 0, 14, 0, 0, 0, 4]);
-    prefix[20..24].copy_from_slice(message.as_bytes());
+    /// Solution ledger (JSON lines)
+    #[arg(long, global = true, env = "FCM_HISTORY", default_value_os_t = history::default_path())]
+    history: PathBuf,
 
-    prefix[24..32].copy_from_slice(&[0, 0, 0, 13, 0, 0, 0, 32]);
-    prefix[32..64].copy_from_slice(&prev_hash);
+    #[command(flatten)]
+    log: logging::LogArgs,
 
-    prefix[64..].copy_from_slice(&[0, 0, 0, 5]);
+    /// TOML file whose settings replace the built-in defaults
+    #[arg(long, global = true, env = "FCM_CONFIG")]
+    config: Option<PathBuf>,
 
-    prefix
+    /// Profile of the config file to use (defaults to its `profile` key)
+    #[arg(long, global = true, env = "FCM_CONFIG_PROFILE")]
+    config_profile: Option<String>,
+
+    /// Do not record solves in the ledger
+    #[arg(long, global = true)]
+    no_history: bool,
 }
 
-fn build_suffix() -> [u8; 44] {
-    let miner: [u8; 32] = [
-        71, 91, 242, 164, 88, 135, 40, 119, 138, 130, 113, 54, 158, 224, 57, 86, 17, 3, 255, 206,
-        53, 73, 64, 44, 224, 164, 121, 206, 191, 27, 9, 245,
-    ];
+/// Options shared by everything that runs worker threads.
+#[derive(clap::Args, Clone)]
+pub struct MinerArgs {
+    /// Worker threads (defaults to the tuned profile)
+    #[arg(short = 'j', long, env = "FCM_THREADS")]
+    pub threads: Option<usize>,
+
+    /// Nonces per batch between stop checks (defaults to the tuned profile)
+    #[arg(long, value_parser = clap::value_parser!(u64).range(tune::MIN_BATCH_SIZE..))]
+    pub batch_size: Option<u64>,
+
+    /// Percent of each worker's time spent hashing
+    #[arg(long, env = "FCM_CPU_PERCENT", default_value_t = 100, value_parser = clap::value_parser!(u32).range(1..=100))]
+    pub cpu_percent: u32,
+
+    /// Cap on the total hashrate in MH/s
+    #[arg(long, env = "FCM_MAX_HASHRATE")]
+    pub max_hashrate: Option<f64>,
+
+    /// Back off while other processes need the CPU
+    #[arg(long, env = "FCM_IDLE")]
+    pub idle: bool,
+
+    /// Report the chance of solving within this many seconds
+    #[arg(long, default_value_t = 60)]
+    pub eta_window: u64,
+
+    /// Keccak implementation to hash with (defaults to the tuned profile)
+    #[arg(long, env = "FCM_ENGINE", value_enum)]
+    pub engine: Option<Engine>,
+
+    /// Measure engines, thread counts and batch sizes again and cache the result
+    #[arg(long)]
+    pub tune: bool,
+
+    /// Per-host tuning profile
+    #[arg(long, env = "FCM_PROFILE", default_value_os_t = tune::default_path())]
+    pub profile: PathBuf,
+
+    /// Order nonces are searched in; random orders keep uncoordinated hosts apart
+    #[arg(long, value_enum, default_value_t = Order::Asc)]
+    pub order: Order,
+
+    /// Seed for the random orders (defaults to a fresh one, printed at startup)
+    #[arg(long)]
+    pub seed: Option<u64>,
+
+    /// Serve Prometheus metrics on this address, e.g. 127.0.0.1:9100
+    #[cfg(feature = "metrics")]
+    #[arg(long)]
+    pub metrics_addr: Option<std::net::SocketAddr>,
+
+    /// Keep rates up to date without printing them
+    #[arg(skip)]
+    pub quiet: bool,
+}
 
-    let mut suffix = [0; 44];
+#[derive(Subcommand)]
+enum Command {
+    /// Summarize past solves from the ledger
+    Stats,
+    /// Inspect the solution ledger
+    History {
+        #[command(subcommand)]
+        action: history::Command,
+    },
+    /// Coordinate several machines mining the same block
+    Pool {
+        #[command(subcommand)]
+        action: pool::Command,
+    },
+    /// Mine nonce ranges leased from a pool
+    Worker(pool::WorkerArgs),
+    /// Keep mining and take jobs over a local HTTP/JSON API
+    Daemon(daemon::DaemonArgs),
+    /// Follow the contract over RPC and print a job line for each new block
+    Watch(watch::RpcArgs),
+    /// Check the configured RPC endpoints or send a transaction through them
+    Rpc {
+        #[command(subcommand)]
+        action: watch::Command,
+    },
+    /// Inspect the settings read from `--config`
+    Config {
+        #[command(subcommand)]
+        action: config::Command,
+    },
+}
 
-    suffix[..12].copy_from_slice(&[0, 0, 0, 18, 0, 0, 0, 0, 0, 0, 0, 0]);
-    suffix[12..].copy_from_slice(&miner);
+// Live throttle adjustments, one command per line:
+// `cpu <percent>`, `hashrate <MH/s, 0 = off>`, `idle on|off`
+fn spawn_stdin_control(throttle: Arc<Throttle>) {
+    thread::spawn(move || {
+        for line in std::io::stdin().lock().lines() {
+            let Ok(line) = line else {
+                break;
+            };
+
+            let mut words = line.split_whitespace();
+
+            match (words.next(), words.next()) {
+                (Some("cpu"), Some(value)) => match value.parse() {
+                    Ok(percent) => throttle.set_cpu_percent(percent),
+                    Err(_) => warn!("Invalid cpu percent: {}", value),
+                },
+                (Some("hashrate"), Some(value)) => match value.parse::<f64>() {
+                    Ok(mhs) => throttle.set_max_hashrate((mhs * 1_000_000.0) as u64),
+                    Err(_) => warn!("Invalid hashrate: {}", value),
+                },
+                (Some("idle"), Some(value)) => throttle.set_idle(value == "on"),
+                (None, _) => {}
+                _ => warn!("Unknown command: {}", line),
+            }
+        }
+    });
+}
+
+/// Worker threads with their throttle and a running hashrate reporter.
+/// `history` is the ledger submission outcomes are read from, if any.
+pub fn start_miner(args: &MinerArgs, history: Option<&Path>) -> Arc<Miner> {
+    if let Err(err) = engine::self_test() {
+        fail(MinerError::SelfTest(err));
+    }
 
-    suffix
+    let profile = load_profile(args);
+    let threads = args
+        .threads
+        .or(profile.as_ref().map(|profile| profile.threads))
+        .unwrap_or_else(num_cpus::get)
+        .max(1);
+    let engine = args
+        .engine
+        .or(profile.as_ref().map(|profile| profile.engine))
+        .unwrap_or_default();
+    let batch_size = args
+        .batch_size
+        .or(profile.as_ref().map(|profile| profile.batch_size))
+        .unwrap_or(miner::DEFAULT_BATCH_SIZE);
+
+    info!("Engine {}, {} threads, batch {}", engine.name(), threads, batch_size);
+
+    let max_hashrate = args.max_hashrate.map_or(0, |mhs| (mhs * 1_000_000.0) as u64);
+    let throttle = Arc::new(Throttle::new(threads, args.cpu_percent, max_hashrate, args.idle));
+
+    throttle.spawn_idle_monitor();
+    spawn_stdin_control(throttle.clone());
+
+    let stats = Arc::new(Stats::new(threads));
+    stats.spawn_reporter(args.eta_window, !args.quiet);
+
+    #[cfg(feature = "metrics")]
+    if let Some(addr) = args.metrics_addr {
+        metrics::spawn_server(addr, stats.clone(), history.map(Path::to_path_buf));
+    }
+
+    #[cfg(not(feature = "metrics"))]
+    let _ = history;
+
+    let seed = args.seed.unwrap_or_else(random_seed);
+
+    if matches!(args.order, Order::RandomStart | Order::Permuted) {
+        let order = args.order.to_possible_value().unwrap();
+        info!("Search order {}, seed {}", order.get_name(), seed);
+    }
+
+    Arc::new(Miner {
+        threads,
+        batch_size,
+        engine,
+        order: args.order,
+        seed,
+        throttle,
+        stats,
+    })
 }
 
-fn count_leading_hex_zeros(hash: &[u8]) -> usize {
-    // First 8 bytes
-    let first_u64 = u64::from_be_bytes([
-        hash[0], hash[1], hash[2], hash[3], hash[4], hash[5], hash[6], hash[7],
-    ]);
+// The cached profile, calibrating first when there is none or `--tune` asks
+fn load_profile(args: &MinerArgs) -> Option<tune::Profile> {
+    let overridden = args.threads.is_some() && args.engine.is_some() && args.batch_size.is_some();
 
-    let leading_zeros = first_u64.leading_zeros();
-    let first_count = (leading_zeros as usize) / 4;
+    if !args.tune {
+        if overridden {
+            return None;
+        }
 
-    // If we used all first 8 bytes, check next 8
-    if first_count == 16 {
-        let second_u64 = u64::from_be_bytes([
-            hash[8], hash[9], hash[10], hash[11], hash[12], hash[13], hash[14], hash[15],
-        ]);
-        first_count + (second_u64.leading_zeros() as usize) / 4
-    } else {
-        first_count
+        if let Some(profile) = tune::load(&args.profile) {
+            return Some(profile);
+        }
     }
+
+    let profile = tune::calibrate();
+
+    if let Err(err) = tune::save(&args.profile, &profile) {
+        error!("Failed to write {}: {}", args.profile.display(), err);
+    }
+
+    Some(profile)
 }
 
-fn main() {
-    let counter = Arc::new(AtomicU64::new(0));
-    // let start_time = Instant::now();
+fn random_seed() -> u64 {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_nanos() as u64);
 
-    let args = Args::parse();
+    // Mix in the host and process so machines started together still differ
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    std::hash::Hash::hash(&(nanos, std::process::id(), history::hostname()), &mut hasher);
 
-    let index = args.index;
-    let prev_hash = hex::decode(args.prev_hash).unwrap().try_into().unwrap();
-    let target_zeros = args.target_zeros;
+    std::hash::Hasher::finish(&hasher)
+}
 
-    let num_threads = num_cpus::get();
+fn main() {
+    let argv: Vec<OsString> = std::env::args_os().collect();
+    let config = config::load(&argv).unwrap_or_else(|err| fail(err));
+    let matches = config::parse(config::apply(Args::command(), &config), &argv);
+    let args = Args::from_arg_matches(&matches).unwrap_or_else(|err| err.exit());
+    logging::init(&args.log);
+
+    let history = (!args.no_history).then_some(args.history.as_path());
+
+    let result = match args.command {
+        Some(Command::Stats) => history::load_ledger(&args.history)
+            .map(|solves| print!("{}", estimate::Summary::new(&solves))),
+        Some(Command::History { action }) => history::run(&args.history, action),
+        Some(Command::Pool { action }) => pool::run(action, history),
+        Some(Command::Worker(ref worker)) => {
+            pool::work(worker, history);
+            Ok(())
+        }
+        Some(Command::Daemon(ref daemon)) => config
+            .network()
+            .and_then(|network| daemon::run(daemon, history, network)),
+        Some(Command::Watch(ref rpc)) => config.network().and_then(|network| watch::run(rpc, network.as_ref())),
+        Some(Command::Rpc { action }) => config
+            .network()
+            .and_then(|network| watch::command(action, network.as_ref())),
+        Some(Command::Config { ref action }) => config::run(&config, resolved(&args), action),
+        None => {
+            mine(&args, history);
+            Ok(())
+        }
+    };
 
-    let mut handles = vec![];
+    if let Err(err) = result {
+        fail(err);
+    }
+}
 
-    let prefix = build_prefix(index, prev_hash);
-    let suffix = build_suffix();
-    let mut hasher_buffer = [0; 68 + 8 + 44];
+/// Log `err` and send it as a JSON event on stdout, where wrappers read results.
+pub fn report(err: &MinerError) {
+    match logging::job() {
+        Some((job, miner)) => error!(job, miner = %miner, error = err.kind(), "{}", err),
+        None => error!(error = err.kind(), "{}", err),
+    }
 
-    // Copy static data into buffer
-    hasher_buffer[..prefix.len()].copy_from_slice(&prefix);
-    hasher_buffer[prefix.len() + 8..].copy_from_slice(&suffix);
+    println!("{}", err.to_event());
+}
 
-    // Spawn worker threads
-    for thread_id in 0..num_threads {
-        let counter = counter.clone();
+/// Report `err` and exit with its code; see [`MinerError`] for the codes.
+pub fn fail(err: MinerError) -> ! {
+    report(&err);
+    std::process::exit(err.exit_code());
+}
 
-        let handle = thread::spawn(move || {
-            let mut hash = [0u8; 32];
-            let mut nonce_start = thread_id;
+// Every nonce in the range was tried without a solution
+fn exhausted(range: NonceRange, hashes: u64) {
+    let event = serde_json::json!({
+        "exhausted": true,
+        "start": range.start,
+        "end": range.last,
+        "hashes": hashes,
+    });
+
+    println!("{}", event);
+    warn!("Searched nonces {}..={} without a solution", range.start, range.last);
+
+    std::process::exit(error::EXIT_EXHAUSTED);
+}
 
-            loop {
-                let nonce_end = nonce_start + BATCH_SIZE;
+// The job from the command line; clap has already made sure every part is present
+fn parse_job(args: &Args) -> Result<Job, MinerError> {
+    Ok(Job {
+        index: args.index.unwrap_or_default(),
+        prev_hash: job::parse_hash("prev_hash", args.prev_hash.as_deref().unwrap_or_default())?,
+        difficulty: job::check_difficulty(args.target_zeros.unwrap_or_default())?,
+        miner: strkey::decode(strkey::ACCOUNT, &args.miner)
+            .map_err(|reason| MinerError::InvalidStrkey { field: "miner", reason })?,
+        message: message(args),
+    })
+}
 
-                // Process entire batch
-                for nonce in nonce_start..nonce_end {
-                    hasher_buffer[68..68 + 8].copy_from_slice(&nonce.to_be_bytes());
+// The message as mined, with `{host}` and `{worker}` filled in
+fn message(args: &Args) -> String {
+    expand_message(&args.message, &history::hostname(), &args.worker.clone().unwrap_or_else(history::hostname))
+}
 
-                    let mut keccak = Keccak::v256();
-                    keccak.update(&hasher_buffer);
-                    keccak.finalize(&mut hash);
+// What `config show` reports: the settings a run with these arguments mines with,
+// resolved from the environment, flags and file the same way
+fn resolved(args: &Args) -> config::Settings {
+    let name = |value: Option<PossibleValue>| value.map(|value| value.get_name().to_string());
+    let miner_args = &args.miner_args;
+
+    config::Settings {
+        miner: Some(args.miner.clone()),
+        message: Some(message(args)),
+        worker: args.worker.clone(),
+        threads: miner_args.threads,
+        batch_size: miner_args.batch_size,
+        engine: name(miner_args.engine.and_then(|engine| engine.to_possible_value())),
+        cpu_percent: Some(miner_args.cpu_percent),
+        max_hashrate: miner_args.max_hashrate,
+        idle: Some(miner_args.idle),
+        order: name(miner_args.order.to_possible_value()),
+        eta_window: Some(miner_args.eta_window),
+        tui: Some(args.tui),
+        history: Some(args.history.clone()),
+        log_level: name(args.log.log_level.to_possible_value()),
+        log_format: name(args.log.log_format.to_possible_value()),
+        log_file: args.log.log_file.clone(),
+        ..config::Settings::default()
+    }
+}
 
-                    if count_leading_hex_zeros(&hash) == target_zeros {
-                        println!("[{}, \"{}\"]", nonce, hex::encode(hash));
-                        counter.fetch_add(1, Ordering::Relaxed); // Count the final hash
-                        std::process::exit(0);
-                    }
-                }
+fn mine(args: &Args, history: Option<&Path>) {
+    let job = parse_job(args).unwrap_or_else(|err| fail(err));
+    let target_zeros = job.difficulty;
 
-                // Update counter with batch size after processing
-                counter.fetch_add(BATCH_SIZE_U64, Ordering::Relaxed);
+    // Streaming keeps every worker going past the first solution
+    let stream = args.share_zeros.is_some();
+    let share_zeros = args.share_zeros.unwrap_or(target_zeros).min(target_zeros);
+    let thresholds = if args.thresholds.is_empty() {
+        (share_zeros..=target_zeros).collect()
+    } else {
+        args.thresholds.clone()
+    };
 
-                // Update start nonce
-                nonce_start = nonce_end;
-            }
+    if args.nonce_start > args.nonce_end {
+        fail(MinerError::NonceRange {
+            start: args.nonce_start,
+            end: args.nonce_end,
         });
+    }
 
-        handles.push(handle);
+    let range = NonceRange {
+        start: args.nonce_start,
+        last: args.nonce_end,
+    };
+
+    // A single thread visits nonces in strict order, so the first hit is the lowest
+    let mut miner_args = args.miner_args.clone();
+
+    if args.deterministic {
+        miner_args.threads = Some(1);
+        miner_args.order = Order::Asc;
+        // Nothing host-specific either, so no calibration and no profile written
+        miner_args.tune = false;
+        miner_args.engine.get_or_insert_with(Engine::default);
+        miner_args.batch_size.get_or_insert(miner::DEFAULT_BATCH_SIZE);
     }
 
-    // Monitor hashrate
-    let report_thread = thread::spawn({
-        let counter = counter.clone();
+    // Redrawing in place only makes sense on a terminal; pipes get plain lines
+    let tui = args.tui && std::io::stdout().is_terminal();
+
+    if args.tui && !tui {
+        warn!("stdout is not a terminal, using plain output");
+    }
 
-        move || {
-            let mut last_counter = 0u64;
-            let mut last_time = Instant::now();
+    miner_args.quiet = tui;
 
-            loop {
-                thread::sleep(Duration::from_secs(2));
+    let miner = start_miner(&miner_args, history);
+    miner.stats.begin_job(job.index, target_zeros);
+    logging::set_job(job.index, &args.miner);
+    info!(job = job.index, miner = %args.miner, "Mining block {}, difficulty {}", job.index, target_zeros);
 
-                let current = counter.load(Ordering::Relaxed);
-                let elapsed = last_time.elapsed().as_secs_f64();
-                let hashes = (current - last_counter) as f64;
+    let dashboard = tui.then(|| {
+        tui::Dashboard::start(
+            job.clone(),
+            miner.stats.clone(),
+            miner_args.eta_window,
+            history.map(Path::to_path_buf),
+        )
+    });
+    let finish_dashboard = || {
+        if let Some(dashboard) = &dashboard {
+            dashboard.finish();
+        }
+    };
+
+    let start_time = Instant::now();
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let (hits_tx, hits_rx) = mpsc::channel();
+    let search = miner.spawn(
+        job.clone(),
+        range,
+        share_zeros,
+        move |hit| {
+            let _ = hits_tx.send(hit);
+        },
+        stop.clone(),
+    );
+
+    let record_solve = |nonce: u64, hash: [u8; 32], hashes: u64| {
+        if args.no_history {
+            return;
+        }
 
-                // Convert to MH/s
-                let hashrate = hashes / elapsed / 1_000_000.0;
+        let seconds = start_time.elapsed().as_secs_f64();
+        let mut solve = Solve::new(&job, nonce, hash, seconds, hashes, miner.threads);
+        solve.engine = miner.engine.name().to_string();
+        solve.order = miner.order;
+        solve.nonce_start = range.start;
 
-                println!("Hashrate: {:.2} MH/s", hashrate);
+        if let Err(err) = history::append_solve(&args.history, solve) {
+            error!(job = job.index, miner = %args.miner, "Failed to write {}: {}", args.history.display(), err);
+        }
+    };
+
+    if stream {
+        let mut shares = 0u64;
+
+        for hit in hits_rx {
+            shares += 1;
+
+            // Shares arrive at hashrate / 16^share_zeros, so their count alone
+            // proves the rate independently of what the workers report
+            let elapsed = start_time.elapsed().as_secs_f64();
+            let proven = shares as f64 * 16f64.powi(share_zeros as i32) / elapsed;
+
+            let event = serde_json::json!({
+                "nonce": hit.nonce,
+                "hash": hex::encode(hit.hash),
+                "message": job.message,
+                "zeros": hit.zeros,
+                "thresholds": thresholds.iter().filter(|&&threshold| hit.zeros >= threshold).collect::<Vec<_>>(),
+                "solution": job.is_solution(hit.zeros),
+                "shares": shares,
+                "proven_hashrate": proven.round(),
+            });
+
+            match &dashboard {
+                Some(dashboard) if job.is_solution(hit.zeros) => dashboard.solved(hit.nonce, &hex::encode(hit.hash)),
+                Some(dashboard) => dashboard.log(format!("Share with {} zeros, nonce {}", hit.zeros, hit.nonce)),
+                None => println!("{}", event),
+            }
 
-                last_counter = current;
-                last_time = Instant::now();
+            if job.is_solution(hit.zeros) {
+                miner.stats.record_solution();
+                record_solve(hit.nonce, hit.hash, miner.stats.snapshot().total());
             }
         }
-    });
 
-    // Wait for solution
-    for handle in handles {
-        handle.join().unwrap();
+        // The channel closes once the whole range has been searched
+        let outcome = search.join();
+        finish_dashboard();
+
+        if let Ok(Outcome::Exhausted) = outcome {
+            exhausted(range, miner.stats.snapshot().total());
+        }
+
+        return;
     }
 
-    report_thread.join().unwrap();
+    // Wait for solution
+    let Some(hit) = hits_rx.iter().find(|hit| job.is_solution(hit.zeros)) else {
+        let outcome = search.join();
+        finish_dashboard();
+
+        if let Ok(Outcome::Exhausted) = outcome {
+            exhausted(range, miner.stats.snapshot().total());
+        }
+
+        return;
+    };
 
-    // let elapsed = start_time.elapsed();
-    // let total_hashes = counter.load(Ordering::Relaxed);
+    stop.store(true, Ordering::Relaxed);
+    miner.stats.record_solution();
+
+    if let Some(dashboard) = &dashboard {
+        dashboard.solved(hit.nonce, &hex::encode(hit.hash));
+        dashboard.finish();
+    }
+    job.print_solution(hit.nonce, &hit.hash);
+
+    let elapsed = start_time.elapsed();
+    let total_hashes = miner.stats.snapshot().total();
+
+    info!(
+        job = job.index,
+        miner = %args.miner,
+        nonce = hit.nonce,
+        "Found solution in {:.2}s after {} hashes ({:.2} MH/s), luck {:.2}",
+        elapsed.as_secs_f64(),
+        total_hashes,
+        total_hashes as f64 / elapsed.as_secs_f64() / 1_000_000.0,
+        estimate::luck(target_zeros, total_hashes)
+    );
+
+    record_solve(hit.nonce, hit.hash, total_hashes);
+
+    if args.deterministic {
+        info!("Reproduce with: {}", repro_command(args, &job, hit.nonce));
+    }
+}
 
-    // println!("Found solution in {:?}", elapsed);
-    // println!("Total hashes: {}", total_hashes);
-    // println!("Average hashrate: {:.2} MH/s",
-    //     (total_hashes as f64 / elapsed.as_secs_f64()) / 1_000_000.0);
+// Command line that finds `nonce` again, searching no further than it
+fn repro_command(args: &Args, job: &Job, nonce: u64) -> String {
+    let quote = |value: &str| format!("'{}'", value.replace('\'', "'\\''"));
+
+    format!(
+        "{} --deterministic --no-history -i {} -p {} -t {} --miner {} --message {} --nonce-start {} --nonce-end {}",
+        env!("CARGO_PKG_NAME"),
+        job.index,
+        hex::encode(job.prev_hash),
+        job.difficulty,
+        args.miner,
+        quote(&job.message),
+        args.nonce_start,
+        nonce
+    )
 }
@@ -0,0 +1,179 @@
+//! Prometheus text exposition of the miner's counters over plain HTTP.
+
+use clap::ValueEnum;
+use std::fmt::Write as _;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, SystemTime};
+use tracing::{info, warn};
+
+use crate::error::MinerError;
+use crate::history::{self, Status};
+use crate::stats::Stats;
+
+// How long a client may take to send its request before it is dropped
+const READ_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Submission counts from the ledger, reloaded only when the file changes.
+#[derive(Default)]
+struct Ledger {
+    // Modification time and length of the file the counts were taken from
+    version: Option<(SystemTime, u64)>,
+    counts: Option<Vec<(Status, usize)>>,
+}
+
+impl Ledger {
+    fn counts(&mut self, path: &Path) -> Option<&[(Status, usize)]> {
+        let version = std::fs::metadata(path).and_then(|meta| Ok((meta.modified()?, meta.len()))).ok();
+
+        if version.is_none() || version != self.version {
+            self.version = version;
+            self.counts = history::load(path).ok().map(|solves| {
+                Status::value_variants()
+                    .iter()
+                    .map(|status| (*status, solves.iter().filter(|solve| solve.status == *status).count()))
+                    .collect()
+            });
+        }
+
+        self.counts.as_deref()
+    }
+}
+
+/// Answer `GET /metrics` on `addr` from a background thread.
+pub fn spawn_server(addr: SocketAddr, stats: Arc<Stats>, history: Option<PathBuf>) {
+    let listener = match TcpListener::bind(addr) {
+        Ok(listener) => listener,
+        Err(err) => crate::fail(MinerError::Io {
+            target: addr.to_string(),
+            source: err,
+        }),
+    };
+
+    info!("Metrics on http://{}/metrics", addr);
+
+    thread::spawn(move || {
+        let mut ledger = Ledger::default();
+
+        for stream in listener.incoming().flatten() {
+            // Scrapes are rare and cheap, so one at a time is enough as long as
+            // a stalled client cannot hold up the rest
+            let ledger = history.as_deref().map(|path| (path, &mut ledger));
+
+            if let Err(err) = respond(stream, &stats, ledger) {
+                warn!("Metrics request failed: {}", err);
+            }
+        }
+    });
+}
+
+fn respond(mut stream: TcpStream, stats: &Stats, ledger: Option<(&Path, &mut Ledger)>) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    stream.set_write_timeout(Some(READ_TIMEOUT))?;
+
+    let mut request = String::new();
+    BufReader::new(&stream).read_line(&mut request)?;
+
+    let (status, content_type, body) = match request.split_whitespace().nth(1) {
+        Some("/metrics") => ("200 OK", "text/plain; version=0.0.4", render(stats, ledger)),
+        _ => ("404 Not Found", "text/plain", String::from("not found\n")),
+    };
+
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    )
+}
+
+fn render(stats: &Stats, ledger: Option<(&Path, &mut Ledger)>) -> String {
+    let snapshot = stats.snapshot();
+    let rates = stats.rates();
+    let mut out = String::new();
+
+    metric(&mut out, "fcm_hashes_total", "counter", "Hashes computed since start.");
+    let _ = writeln!(out, "fcm_hashes_total {}", snapshot.total());
+
+    metric(&mut out, "fcm_thread_hashes_total", "counter", "Hashes computed per worker thread.");
+    for (thread, hashes) in snapshot.hashes.iter().enumerate() {
+        let _ = writeln!(out, "fcm_thread_hashes_total{{thread=\"{}\"}} {}", thread, hashes);
+    }
+
+    metric(&mut out, "fcm_hashrate", "gauge", "Total hashrate in H/s over the last report interval.");
+    let _ = writeln!(out, "fcm_hashrate {}", rates.total);
+
+    metric(&mut out, "fcm_thread_hashrate", "gauge", "Hashrate per worker thread in H/s.");
+    for (thread, rate) in rates.per_thread.iter().enumerate() {
+        let _ = writeln!(out, "fcm_thread_hashrate{{thread=\"{}\"}} {}", thread, rate);
+    }
+
+    metric(&mut out, "fcm_job_index", "gauge", "Block index being mined.");
+    let _ = writeln!(out, "fcm_job_index {}", stats.job_index());
+
+    metric(&mut out, "fcm_job_difficulty", "gauge", "Leading zeros the current block needs.");
+    let _ = writeln!(out, "fcm_job_difficulty {}", stats.difficulty());
+
+    metric(&mut out, "fcm_best_zeros", "gauge", "Most leading zeros found for the current block.");
+    let _ = writeln!(out, "fcm_best_zeros {}", stats.best_zeros());
+
+    metric(&mut out, "fcm_solutions_total", "counter", "Solutions found since start.");
+    let _ = writeln!(out, "fcm_solutions_total {}", stats.solutions());
+
+    metric(
+        &mut out,
+        "fcm_job_switch_latency_seconds",
+        "gauge",
+        "Time from the last job arriving to workers hashing it.",
+    );
+    let _ = writeln!(out, "fcm_job_switch_latency_seconds {}", stats.switch_latency().as_secs_f64());
+
+    let endpoints = stats.rpc_health();
+
+    if !endpoints.is_empty() {
+        metric(&mut out, "fcm_rpc_requests_total", "counter", "Requests sent to each RPC endpoint.");
+        for health in &endpoints {
+            let _ = writeln!(out, "fcm_rpc_requests_total{{endpoint=\"{}\"}} {}", health.url, health.requests);
+        }
+
+        metric(&mut out, "fcm_rpc_errors_total", "counter", "Requests to each RPC endpoint that failed.");
+        for health in &endpoints {
+            let _ = writeln!(out, "fcm_rpc_errors_total{{endpoint=\"{}\"}} {}", health.url, health.errors);
+        }
+
+        metric(&mut out, "fcm_rpc_latency_seconds", "gauge", "Smoothed response time of each RPC endpoint.");
+        for health in &endpoints {
+            let _ = writeln!(out, "fcm_rpc_latency_seconds{{endpoint=\"{}\"}} {}", health.url, health.latency);
+        }
+
+        metric(&mut out, "fcm_rpc_latest_ledger", "gauge", "Newest ledger each RPC endpoint has reported.");
+        for health in &endpoints {
+            let _ = writeln!(out, "fcm_rpc_latest_ledger{{endpoint=\"{}\"}} {}", health.url, health.latest_ledger);
+        }
+
+        metric(&mut out, "fcm_rpc_up", "gauge", "Whether each RPC endpoint is in use rather than backing off.");
+        for health in &endpoints {
+            let _ = writeln!(out, "fcm_rpc_up{{endpoint=\"{}\"}} {}", health.url, health.up as u8);
+        }
+    }
+
+    // Outcomes live in the ledger, where the submitter records them
+    if let Some(counts) = ledger.and_then(|(path, ledger)| ledger.counts(path)) {
+        metric(&mut out, "fcm_submissions", "gauge", "Ledger solves by submission status.");
+        for (status, count) in counts {
+            let _ = writeln!(out, "fcm_submissions{{status=\"{}\"}} {}", status, count);
+        }
+    }
+
+    out
+}
+
+fn metric(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
@@ -0,0 +1,220 @@
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Instant;
+
+use crate::engine::Engine;
+use crate::job::{count_leading_hex_zeros, Job};
+use crate::stats::Stats;
+use crate::throttle::Throttle;
+
+/// Nonces a worker hashes between checks for a stop or new job.
+pub const DEFAULT_BATCH_SIZE: u64 = 50_000;
+
+/// Inclusive range of nonces.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct NonceRange {
+    pub start: u64,
+    pub last: u64,
+}
+
+impl NonceRange {
+    pub const FULL: Self = Self {
+        start: 0,
+        last: u64::MAX,
+    };
+
+    /// Nonces in the range, never zero since both ends are included.
+    pub fn count(&self) -> u128 {
+        (self.last - self.start) as u128 + 1
+    }
+}
+
+/// Order batches of nonces are visited in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum Order {
+    /// From the start of the range up
+    #[default]
+    Asc,
+    /// From the end of the range down, a batch at a time
+    Desc,
+    /// Upwards from a seeded offset, wrapping around
+    RandomStart,
+    /// A seeded pseudorandom permutation of the whole range
+    Permuted,
+}
+
+// Maps the i-th batch visited to the batch it covers, a bijection on 0..count
+struct Batches {
+    count: u64,
+    order: Order,
+    offset: u64,
+    bits: u32,
+    keys: [u64; 4],
+}
+
+impl Batches {
+    fn new(count: u64, order: Order, seed: u64) -> Self {
+        let mut state = seed;
+        let keys = [(); 4].map(|()| splitmix64(&mut state));
+
+        Self {
+            count,
+            order,
+            offset: splitmix64(&mut state) % count.max(1),
+            // Smallest power of two covering every batch
+            bits: (64 - count.saturating_sub(1).leading_zeros()).max(1),
+            keys,
+        }
+    }
+
+    fn get(&self, i: u64) -> u64 {
+        match self.order {
+            Order::Asc => i,
+            Order::Desc => self.count - 1 - i,
+            Order::RandomStart => (i + self.offset) % self.count,
+            Order::Permuted => {
+                // Cycle-walk a permutation of 0..2^bits until it lands in range
+                let mut batch = self.permute(i);
+
+                while batch >= self.count {
+                    batch = self.permute(batch);
+                }
+
+                batch
+            }
+        }
+    }
+
+    // Multiply by odd numbers and xor-shift, each a bijection modulo 2^bits
+    fn permute(&self, mut x: u64) -> u64 {
+        let mask = u64::MAX >> (64 - self.bits);
+        let shift = self.bits / 2 + 1;
+
+        for key in self.keys {
+            x = (x.wrapping_mul(key | 1).wrapping_add(key >> 32)) & mask;
+            x ^= x >> shift;
+        }
+
+        x
+    }
+}
+
+fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// A hash with at least the reported number of zeros.
+pub struct Hit {
+    pub nonce: u64,
+    pub hash: [u8; 32],
+    pub zeros: usize,
+}
+
+pub enum Outcome {
+    Exhausted,
+    Stopped,
+}
+
+pub struct Miner {
+    pub threads: usize,
+    pub batch_size: u64,
+    pub engine: Engine,
+    pub order: Order,
+    pub seed: u64,
+    pub throttle: Arc<Throttle>,
+    pub stats: Arc<Stats>,
+}
+
+impl Miner {
+    /// Search `range` in the background, passing every hash with at least
+    /// `report_zeros` zeros to `on_hit` until the range runs out or `stop` is set.
+    pub fn spawn(
+        self: &Arc<Self>,
+        job: Job,
+        range: NonceRange,
+        report_zeros: usize,
+        on_hit: impl Fn(Hit) + Send + Sync + 'static,
+        stop: Arc<AtomicBool>,
+    ) -> JoinHandle<Outcome> {
+        let miner = self.clone();
+
+        thread::spawn(move || miner.search(&job, range, report_zeros, &on_hit, &stop))
+    }
+
+    pub fn search(
+        &self,
+        job: &Job,
+        range: NonceRange,
+        report_zeros: usize,
+        on_hit: &(impl Fn(Hit) + Sync),
+        stop: &AtomicBool,
+    ) -> Outcome {
+        // With two or more nonces per batch even the full range, 2^64 nonces,
+        // splits into at most 2^63 batches, so the count fits in a u64
+        let batch_size = self.batch_size.max(2);
+        let batches = (range.count().div_ceil(batch_size as u128)) as u64;
+        let order = Batches::new(batches, self.order, self.seed);
+        let order = &order;
+
+        self.stats.job_started();
+
+        thread::scope(|scope| {
+            for thread_id in 0..self.threads {
+                scope.spawn(move || {
+                    let slot = self.stats.slot(thread_id);
+                    let mut preimage = job.preimage(self.engine);
+                    let mut hash = [0u8; 32];
+                    let mut hashes = slot.hashes();
+
+                    // Whole batches are dealt round-robin so threads never overlap
+                    let mut i = thread_id as u64;
+
+                    while i < batches && !stop.load(Ordering::Relaxed) {
+                        let first = range.start + order.get(i) * batch_size;
+                        let last = first.saturating_add(batch_size - 1).min(range.last);
+                        let batch_start = Instant::now();
+                        let mut best = (0, first);
+
+                        for nonce in first..=last {
+                            preimage.hash(nonce, &mut hash);
+
+                            let zeros = count_leading_hex_zeros(&hash);
+                            if zeros > best.0 {
+                                best = (zeros, nonce);
+                            }
+
+                            if zeros >= report_zeros {
+                                slot.record(hashes + (nonce - first) + 1, nonce); // Count the found hash
+                                on_hit(Hit { nonce, hash, zeros });
+                            }
+                        }
+
+                        // Publish this worker's total after processing
+                        hashes += last - first + 1;
+                        slot.record(hashes, last);
+                        slot.record_best(best.0, best.1);
+
+                        self.throttle.pace(thread_id, batch_start.elapsed(), last - first + 1, stop);
+
+                        i += self.threads as u64;
+                    }
+                });
+            }
+        });
+
+        if stop.load(Ordering::Relaxed) {
+            Outcome::Stopped
+        } else {
+            Outcome::Exhausted
+        }
+    }
+}
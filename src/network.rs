@@ -0,0 +1,83 @@
+//! Stellar networks the FCM contract can live on: the passphrase transactions
+//! are signed for, where to reach RPC, and the contract's address. The miner
+//! never signs; the passphrase is handed on to the `stellar` CLI, which does.
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::MinerError;
+use crate::strkey;
+use crate::xdr;
+
+pub const MAINNET_PASSPHRASE: &str = "Public Global Stellar Network ; September 2015";
+pub const TESTNET_PASSPHRASE: &str = "Test SDF Network ; September 2015";
+pub const FUTURENET_PASSPHRASE: &str = "Test SDF Future Network ; October 2022";
+
+/// Names [`Network::builtin`] knows; anything else has to come from config.
+pub const BUILTIN: &[&str] = &["mainnet", "testnet", "futurenet"];
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Network {
+    #[serde(skip)]
+    pub name: String,
+    pub passphrase: String,
+    /// Tried in order; the first is preferred.
+    #[serde(default)]
+    pub rpc_urls: Vec<String>,
+    /// FCM contract (`C...`), which differs per network.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub contract_id: Option<String>,
+}
+
+impl Network {
+    pub fn builtin(name: &str) -> Option<Network> {
+        let (passphrase, rpc_url) = match name {
+            "mainnet" => (MAINNET_PASSPHRASE, "https://mainnet.sorobanrpc.com"),
+            "testnet" => (TESTNET_PASSPHRASE, "https://soroban-testnet.stellar.org"),
+            "futurenet" => (FUTURENET_PASSPHRASE, "https://rpc-futurenet.stellar.org"),
+            _ => return None,
+        };
+
+        Some(Network {
+            name: name.to_string(),
+            passphrase: passphrase.to_string(),
+            rpc_urls: vec![rpc_url.to_string()],
+            contract_id: None,
+        })
+    }
+
+    /// The decoded contract address, failing when it is missing or malformed.
+    pub fn contract(&self) -> Result<[u8; 32], MinerError> {
+        let Some(contract_id) = &self.contract_id else {
+            return Err(MinerError::Config(format!("network {} has no contract_id", self.name)));
+        };
+
+        strkey::decode(strkey::CONTRACT, contract_id).map_err(|reason| MinerError::InvalidStrkey {
+            field: "contract_id",
+            reason,
+        })
+    }
+
+    /// Check everything a miner needs is present and well formed.
+    pub fn validate(&self) -> Result<(), MinerError> {
+        if self.passphrase.is_empty() {
+            return Err(MinerError::Config(format!("network {} has no passphrase", self.name)));
+        }
+
+        if self.rpc_urls.is_empty() {
+            return Err(MinerError::Config(format!("network {} has no rpc_urls", self.name)));
+        }
+
+        self.contract().map(|_| ())
+    }
+
+    /// Ledger key of the contract instance, which holds the current block.
+    pub fn instance_key(&self) -> Result<Vec<u8>, MinerError> {
+        Ok(xdr::instance_key(&self.contract()?))
+    }
+
+    /// Ledger key of block `index`, which holds its hash.
+    pub fn block_key(&self, index: u64) -> Result<Vec<u8>, MinerError> {
+        Ok(xdr::block_key(&self.contract()?, index))
+    }
+}
@@ -0,0 +1,205 @@
+use clap::{Args, Subcommand};
+use serde::{Deserialize, Serialize};
+use std::io::{self, BufRead, Write};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+
+use crate::error::MinerError;
+use crate::history::{self, Format};
+use crate::job::Job;
+use crate::miner::NonceRange;
+
+mod server;
+mod shares;
+mod worker;
+
+pub use server::serve;
+pub use worker::work;
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Own the current job and lease nonce ranges to remote workers
+    Serve(ServeArgs),
+    /// Split each solved block over the shares that led up to it
+    Payouts {
+        /// Share ledger (JSON lines)
+        #[arg(long, env = "FCM_SHARES", default_value_os_t = shares::default_path())]
+        shares: PathBuf,
+
+        /// Shares counted per block (the N in PPLNS)
+        #[arg(long, default_value_t = 1000)]
+        window: usize,
+
+        /// Machine-readable output instead of a table
+        #[arg(long, value_enum)]
+        format: Option<Format>,
+
+        /// Write to a file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Args)]
+pub struct ServeArgs {
+    /// Address to accept workers on
+    #[arg(long, default_value = "127.0.0.1:7878")]
+    pub listen: SocketAddr,
+
+    /// Nonces handed out per lease
+    #[arg(long, default_value_t = 1 << 30)]
+    pub lease_size: u64,
+
+    /// Seconds a disconnected worker keeps its leases before they are re-leased
+    #[arg(long, default_value_t = 30)]
+    pub lease_grace: u64,
+
+    /// Shared secret workers must present
+    #[arg(long, env = "FCM_POOL_TOKEN")]
+    pub token: Option<String>,
+
+    /// Leading zeros a hash needs to count as a share (capped at the difficulty)
+    #[arg(long, default_value_t = 5)]
+    pub share_zeros: usize,
+
+    /// Share ledger (JSON lines)
+    #[arg(long, env = "FCM_SHARES", default_value_os_t = shares::default_path())]
+    pub shares: PathBuf,
+
+    /// Initial block index; later jobs are read from stdin as JSON lines
+    #[arg(short, long, requires_all = ["prev_hash", "target_zeros"])]
+    pub index: Option<u64>,
+
+    /// Initial previous block hash (hex string)
+    #[arg(short, long)]
+    pub prev_hash: Option<String>,
+
+    /// Initial number of leading zeros required
+    #[arg(short, long)]
+    pub target_zeros: Option<usize>,
+
+    /// Miner address for jobs that do not name one
+    #[arg(long, env = "FCM_MINER", default_value = crate::MINER)]
+    pub miner: String,
+
+    /// Message for jobs that do not carry one
+    #[arg(long, env = "FCM_MESSAGE", default_value = crate::MESSAGE)]
+    pub message: String,
+}
+
+#[derive(Args)]
+pub struct WorkerArgs {
+    /// Pool address (host:port)
+    #[arg(long)]
+    pub pool: String,
+
+    /// Name this worker reports to the pool (defaults to the hostname)
+    #[arg(long)]
+    pub name: Option<String>,
+
+    /// Shared secret the pool expects
+    #[arg(long, env = "FCM_POOL_TOKEN")]
+    pub token: Option<String>,
+
+    #[command(flatten)]
+    pub miner_args: crate::MinerArgs,
+}
+
+/// `history` is the ledger solved blocks are recorded in, if any.
+pub fn run(action: Command, history: Option<&Path>) -> Result<(), MinerError> {
+    match action {
+        Command::Serve(args) => serve(args, history),
+        Command::Payouts { shares, window, format, output } => {
+            let entries = shares::load(&shares).map_err(|err| MinerError::io(&shares, err))?;
+            let payouts = shares::payouts(&entries, window.max(1));
+            let mut out = history::create_output(output.as_deref())?;
+
+            let result = match format {
+                None => shares::write_table(&mut out, &payouts),
+                Some(Format::Csv) => shares::write_csv(&mut out, &payouts),
+                Some(Format::Json) => serde_json::to_writer_pretty(&mut out, &payouts)
+                    .map_err(io::Error::from)
+                    .and_then(|()| writeln!(out)),
+            };
+
+            result.map_err(|err| MinerError::Io {
+                target: String::from("export"),
+                source: err,
+            })
+        }
+    }
+}
+
+/// Worker to pool, one JSON object per line.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Request {
+    Hello {
+        worker: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        token: Option<String>,
+    },
+    Lease,
+    Done {
+        job_id: u64,
+        range: NonceRange,
+        hashes: u64,
+    },
+    Share {
+        job_id: u64,
+        nonce: u64,
+    },
+    Solution {
+        job_id: u64,
+        nonce: u64,
+    },
+}
+
+/// The `Reply::Error` for a bad token, the one error a worker gives up on.
+pub const INVALID_TOKEN: &str = "invalid token";
+
+/// Pool to worker, one JSON object per line.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Reply {
+    Welcome,
+    Job {
+        job_id: u64,
+        job: Job,
+        // Hashes with this many zeros are reported as shares
+        share_zeros: usize,
+    },
+    Lease { job_id: u64, range: NonceRange },
+    Solved { job_id: u64 },
+    Rejected { reason: String },
+    Error { message: String },
+}
+
+pub fn send<T: Serialize>(writer: &mut impl Write, message: &T) -> io::Result<()> {
+    let mut line = serde_json::to_vec(message)?;
+    line.push(b'\n');
+
+    writer.write_all(&line)?;
+    writer.flush()
+}
+
+/// Read the next message, `None` once the peer hangs up.
+pub fn receive<T: for<'de> Deserialize<'de>>(reader: &mut impl BufRead) -> io::Result<Option<T>> {
+    let mut line = String::new();
+
+    loop {
+        line.clear();
+
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+
+        if !line.trim().is_empty() {
+            break;
+        }
+    }
+
+    serde_json::from_str(&line)
+        .map(Some)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
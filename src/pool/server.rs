@@ -0,0 +1,471 @@
+use std::collections::{HashMap, HashSet};
+use std::io::{self, BufRead, BufReader};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::{error, info, warn};
+
+use super::shares::{self, Block, Share};
+use super::{receive, send, Reply, Request, ServeArgs, INVALID_TOKEN};
+use crate::history::{self, Solve};
+use crate::error::MinerError;
+use crate::job::{self, count_leading_hex_zeros, Job};
+use crate::miner::NonceRange;
+use crate::strkey;
+
+const REPORT_INTERVAL: Duration = Duration::from_secs(10);
+
+struct Pool {
+    job: Option<(u64, Job)>,
+    next_job_id: u64,
+    // Next nonce never leased for the current job, `None` once the space is used up
+    cursor: Option<u64>,
+    // Ranges taken back from workers that went away, leased again first
+    free: Vec<NonceRange>,
+    leases: HashMap<String, Vec<NonceRange>>,
+    orphaned: HashMap<String, Instant>,
+    clients: HashMap<String, Sender<Reply>>,
+    hashes: HashMap<String, u64>,
+    job_started: Instant,
+    job_hashes: u64,
+    // Share nonces already credited for the current job
+    seen: HashSet<u64>,
+    lease_size: u64,
+    lease_grace: Duration,
+    share_zeros: usize,
+    shares: PathBuf,
+}
+
+impl Pool {
+    fn set_job(&mut self, job: Job) {
+        let job_id = self.next_job_id;
+        self.next_job_id += 1;
+
+        let miner = strkey::encode(strkey::ACCOUNT, &job.miner);
+        info!(job = job_id, miner = %miner, "Job {}: block {}, difficulty {}", job_id, job.index, job.difficulty);
+        crate::logging::set_job(job_id, &miner);
+
+        self.job = Some((job_id, job.clone()));
+        self.job_started = Instant::now();
+        self.job_hashes = 0;
+        self.cursor = Some(0);
+        self.free.clear();
+        self.seen.clear();
+        self.leases.values_mut().for_each(Vec::clear);
+
+        if let Some(reply) = self.job_reply() {
+            self.broadcast(reply);
+        }
+    }
+
+    fn job_reply(&self) -> Option<Reply> {
+        let (job_id, job) = self.job.as_ref()?;
+
+        Some(Reply::Job {
+            job_id: *job_id,
+            job: job.clone(),
+            share_zeros: self.share_zeros.min(job.difficulty),
+        })
+    }
+
+    fn broadcast(&self, reply: Reply) {
+        for client in self.clients.values() {
+            let _ = client.send(reply.clone());
+        }
+    }
+
+    fn lease(&mut self, worker: &str) -> Option<(u64, NonceRange)> {
+        let job_id = self.job.as_ref()?.0;
+
+        self.reclaim_orphans();
+
+        let range = match self.free.pop() {
+            Some(range) => range,
+            None => {
+                let start = self.cursor?;
+                let last = start.saturating_add(self.lease_size - 1);
+                self.cursor = last.checked_add(1);
+
+                NonceRange { start, last }
+            }
+        };
+
+        self.leases.entry(worker.to_string()).or_default().push(range);
+
+        Some((job_id, range))
+    }
+
+    fn done(&mut self, worker: &str, job_id: u64, range: NonceRange, hashes: u64) -> Result<(), String> {
+        // Leases of an older job were dropped with it, so only the hashes count
+        if self.job.as_ref().is_some_and(|(current, _)| *current == job_id) {
+            let leases = self.leases.entry(worker.to_string()).or_default();
+            let position = leases
+                .iter()
+                .position(|lease| *lease == range)
+                .ok_or_else(|| format!("range {}..={} is not leased to {}", range.start, range.last, worker))?;
+
+            leases.swap_remove(position);
+            self.job_hashes += hashes;
+        }
+
+        *self.hashes.entry(worker.to_string()).or_default() += hashes;
+
+        Ok(())
+    }
+
+    fn share(&mut self, worker: &str, job_id: u64, nonce: u64) -> Result<(), String> {
+        let share_zeros = self.share_zeros;
+
+        let Some((current, job)) = &self.job else {
+            return Err(String::from("stale job"));
+        };
+
+        if *current != job_id {
+            return Err(String::from("stale job"));
+        }
+
+        let share_zeros = share_zeros.min(job.difficulty);
+        let zeros = count_leading_hex_zeros(&job.hash(nonce));
+
+        if zeros < share_zeros {
+            return Err(format!("share has {} zeros", zeros));
+        }
+
+        if !self.seen.insert(nonce) {
+            return Err(String::from("duplicate share"));
+        }
+
+        let share = Share {
+            time: history::now(),
+            worker: worker.to_string(),
+            job_id,
+            index: job.index,
+            nonce,
+            zeros,
+            share_zeros,
+        };
+
+        if let Err(err) = shares::append(&self.shares, &shares::Entry::Share(share)) {
+            error!(job = job_id, worker, "Failed to write {}: {}", self.shares.display(), err);
+        }
+
+        Ok(())
+    }
+
+    fn reclaim_orphans(&mut self) {
+        let grace = self.lease_grace;
+        let expired: Vec<String> = self
+            .orphaned
+            .iter()
+            .filter(|(_, since)| since.elapsed() >= grace)
+            .map(|(worker, _)| worker.clone())
+            .collect();
+
+        for worker in expired {
+            self.orphaned.remove(&worker);
+
+            if let Some(leases) = self.leases.remove(&worker) {
+                if !leases.is_empty() {
+                    warn!("Re-leasing {} ranges from {}", leases.len(), worker);
+                }
+
+                self.free.extend(leases);
+            }
+        }
+    }
+}
+
+pub fn serve(args: ServeArgs, history: Option<&Path>) -> Result<(), MinerError> {
+    let listener = TcpListener::bind(args.listen).map_err(|err| MinerError::Io {
+        target: args.listen.to_string(),
+        source: err,
+    })?;
+
+    info!("Pool listening on {}", args.listen);
+
+    let pool = Arc::new(Mutex::new(Pool {
+        job: None,
+        next_job_id: 1,
+        cursor: None,
+        free: vec![],
+        leases: HashMap::new(),
+        orphaned: HashMap::new(),
+        clients: HashMap::new(),
+        hashes: HashMap::new(),
+        job_started: Instant::now(),
+        job_hashes: 0,
+        seen: HashSet::new(),
+        lease_size: args.lease_size.max(1),
+        lease_grace: Duration::from_secs(args.lease_grace),
+        share_zeros: args.share_zeros,
+        shares: args.shares.clone(),
+    }));
+
+    if let (Some(index), Some(prev_hash), Some(difficulty)) =
+        (args.index, &args.prev_hash, args.target_zeros)
+    {
+        let job = serde_json::json!({
+            "index": index,
+            "prev_hash": prev_hash,
+            "difficulty": difficulty,
+        });
+
+        let job = parse_job(job, &args)?;
+        pool.lock().unwrap().set_job(job);
+    }
+
+    thread::spawn({
+        let pool = pool.clone();
+        let token = args.token.clone();
+        let history = history.map(Path::to_path_buf);
+
+        move || {
+            for stream in listener.incoming().flatten() {
+                let pool = pool.clone();
+                let token = token.clone();
+                let history = history.clone();
+
+                thread::spawn(move || {
+                    let peer = stream.peer_addr().ok();
+
+                    if let Err(err) = handle(stream, &pool, token.as_deref(), history.as_deref()) {
+                        warn!("Connection {:?} failed: {}", peer, err);
+                    }
+                });
+            }
+        }
+    });
+
+    spawn_reporter(pool.clone());
+
+    // New jobs arrive on stdin, one JSON object per line
+    for line in io::stdin().lock().lines() {
+        let Ok(line) = line else {
+            break;
+        };
+
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        match serde_json::from_str(&line)
+            .map_err(|err| MinerError::InvalidJob(err.to_string()))
+            .and_then(|job| parse_job(job, &args))
+        {
+            Ok(job) => pool.lock().unwrap().set_job(job),
+            // The pool keeps its current job, and the wrapper learns why
+            Err(err) => crate::report(&err),
+        }
+    }
+
+    // Keep serving the last job once stdin is closed
+    loop {
+        thread::park();
+    }
+}
+
+// Jobs may leave out the miner and message to use the pool's defaults. Leases
+// already keep workers apart, so message templates are filled in for the pool host
+fn parse_job(job: serde_json::Value, args: &ServeArgs) -> Result<Job, MinerError> {
+    job::from_json(job, &args.miner, &args.message, None)
+}
+
+fn handle(
+    stream: TcpStream,
+    pool: &Mutex<Pool>,
+    token: Option<&str>,
+    history: Option<&Path>,
+) -> io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = stream;
+
+    let worker = match receive(&mut reader)? {
+        Some(Request::Hello { worker, token: given }) => {
+            if token.is_some() && given.as_deref() != token {
+                send(&mut writer, &Reply::Error { message: String::from(INVALID_TOKEN) })?;
+                return Ok(());
+            }
+
+            worker
+        }
+        _ => {
+            send(&mut writer, &Reply::Error { message: String::from("expected hello") })?;
+            return Ok(());
+        }
+    };
+
+    let (tx, rx) = mpsc::channel();
+
+    {
+        let mut pool = pool.lock().unwrap();
+
+        if pool.clients.contains_key(&worker) {
+            send(&mut writer, &Reply::Error { message: format!("worker {} is already connected", worker) })?;
+            return Ok(());
+        }
+
+        // A worker that comes back in time keeps its leases
+        pool.orphaned.remove(&worker);
+        pool.clients.insert(worker.clone(), tx.clone());
+
+        tx.send(Reply::Welcome).unwrap();
+
+        if let Some(reply) = pool.job_reply() {
+            tx.send(reply).unwrap();
+        }
+    }
+
+    info!(worker, "Worker {} connected", worker);
+
+    let writer_thread = thread::spawn(move || {
+        for reply in rx {
+            if send(&mut writer, &reply).is_err() {
+                break;
+            }
+        }
+    });
+
+    let result = serve_worker(&worker, &mut reader, pool, &tx, history);
+
+    {
+        let mut pool = pool.lock().unwrap();
+        pool.clients.remove(&worker);
+        pool.orphaned.insert(worker.clone(), Instant::now());
+    }
+
+    drop(tx);
+    let _ = writer_thread.join();
+
+    info!(worker, "Worker {} disconnected", worker);
+
+    result
+}
+
+fn serve_worker(
+    worker: &str,
+    reader: &mut impl BufRead,
+    pool: &Mutex<Pool>,
+    tx: &Sender<Reply>,
+    history: Option<&Path>,
+) -> io::Result<()> {
+    while let Some(request) = receive(reader)? {
+        let mut pool = pool.lock().unwrap();
+
+        match request {
+            Request::Lease => {
+                match pool.lease(worker) {
+                    Some((job_id, range)) => {
+                        let _ = tx.send(Reply::Lease { job_id, range });
+                    }
+                    // Without a job the worker waits for one, but a used-up
+                    // space will not grow until the next job
+                    None if pool.job.is_some() => {
+                        let _ = tx.send(Reply::Rejected { reason: String::from("nonce space of the job is used up") });
+                    }
+                    None => {}
+                }
+            }
+            Request::Done { job_id, range, hashes } => {
+                if let Err(reason) = pool.done(worker, job_id, range, hashes) {
+                    let _ = tx.send(Reply::Rejected { reason });
+                }
+            }
+            Request::Share { job_id, nonce } => {
+                if let Err(reason) = pool.share(worker, job_id, nonce) {
+                    let _ = tx.send(Reply::Rejected { reason });
+                }
+            }
+            Request::Solution { job_id, nonce } => {
+                let reply = match &pool.job {
+                    Some((current, job)) if *current == job_id => {
+                        let hash = job.hash(nonce);
+                        let zeros = count_leading_hex_zeros(&hash);
+
+                        if job.is_solution(zeros) {
+                            let miner = strkey::encode(strkey::ACCOUNT, &job.miner);
+
+                            job.print_solution(nonce, &hash);
+                            info!(job = job_id, miner = %miner, worker, nonce, "Worker {} solved block {}", worker, job.index);
+
+                            if let Some(path) = history {
+                                let mut solve = Solve::new(
+                                    job,
+                                    nonce,
+                                    hash,
+                                    pool.job_started.elapsed().as_secs_f64(),
+                                    pool.job_hashes,
+                                    pool.clients.len(),
+                                );
+                                solve.host = format!("{} (pool)", worker);
+
+                                if let Err(err) = history::append_solve(path, solve) {
+                                    error!(job = job_id, miner = %miner, "Failed to write {}: {}", path.display(), err);
+                                }
+                            }
+
+                            let block = Block {
+                                time: history::now(),
+                                worker: worker.to_string(),
+                                job_id,
+                                index: job.index,
+                                nonce,
+                            };
+
+                            if let Err(err) = shares::append(&pool.shares, &shares::Entry::Block(block)) {
+                                error!(job = job_id, miner = %miner, "Failed to write {}: {}", pool.shares.display(), err);
+                            }
+
+                            pool.job = None;
+                            pool.broadcast(Reply::Solved { job_id });
+                            continue;
+                        }
+
+                        Reply::Rejected { reason: format!("hash has {} zeros", zeros) }
+                    }
+                    _ => Reply::Rejected { reason: String::from("stale job") },
+                };
+
+                let _ = tx.send(reply);
+            }
+            Request::Hello { .. } => {
+                let _ = tx.send(Reply::Error { message: String::from("already said hello") });
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn spawn_reporter(pool: Arc<Mutex<Pool>>) {
+    thread::spawn(move || {
+        let mut last: HashMap<String, u64> = HashMap::new();
+
+        loop {
+            thread::sleep(REPORT_INTERVAL);
+
+            let pool = pool.lock().unwrap();
+
+            if pool.clients.is_empty() {
+                continue;
+            }
+
+            let secs = REPORT_INTERVAL.as_secs_f64();
+            let rate: u64 = pool
+                .hashes
+                .iter()
+                .map(|(worker, total)| total - last.get(worker).copied().unwrap_or(0))
+                .sum();
+
+            info!(
+                "Pool: {} workers, {:.2} MH/s completed",
+                pool.clients.len(),
+                rate as f64 / secs / 1_000_000.0
+            );
+
+            last = pool.hashes.clone();
+        }
+    });
+}
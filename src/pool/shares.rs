@@ -0,0 +1,161 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+use crate::history::{self, csv_field};
+
+/// A verified partial solution, credited to the worker that found it.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Share {
+    pub time: u64,
+    pub worker: String,
+    pub job_id: u64,
+    pub index: u64,
+    pub nonce: u64,
+    pub zeros: usize,
+    // Threshold the share was accepted at, which sets its weight
+    pub share_zeros: usize,
+}
+
+/// A block the pool solved, paid out over the shares before it.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Block {
+    pub time: u64,
+    pub worker: String,
+    pub job_id: u64,
+    pub index: u64,
+    pub nonce: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Entry {
+    Share(Share),
+    Block(Block),
+}
+
+/// One worker's cut of one block.
+#[derive(Serialize)]
+pub struct Payout {
+    pub index: u64,
+    pub nonce: u64,
+    pub worker: String,
+    pub shares: u64,
+    pub fraction: f64,
+}
+
+/// `shares.jsonl` next to the default solution ledger.
+pub fn default_path() -> PathBuf {
+    history::default_path().with_file_name("shares.jsonl")
+}
+
+pub fn append(path: &Path, entry: &Entry) -> io::Result<()> {
+    history::append(path, entry)
+}
+
+pub fn load(path: &Path) -> io::Result<Vec<Entry>> {
+    let reader = match File::open(path) {
+        Ok(file) => BufReader::new(file),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(err) => return Err(err),
+    };
+
+    let mut entries = vec![];
+
+    for (number, line) in reader.lines().enumerate() {
+        let line = line?;
+
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        entries.push(serde_json::from_str(&line).map_err(|err| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}:{}: {}", path.display(), number + 1, err),
+            )
+        })?);
+    }
+
+    Ok(entries)
+}
+
+/// PPLNS: each block is split over the last `window` shares before it, each
+/// share weighted by the work its threshold stands for.
+pub fn payouts(entries: &[Entry], window: usize) -> Vec<Payout> {
+    let mut shares: Vec<&Share> = vec![];
+    let mut payouts = vec![];
+
+    for entry in entries {
+        match entry {
+            Entry::Share(share) => shares.push(share),
+            Entry::Block(block) => {
+                let recent = &shares[shares.len().saturating_sub(window)..];
+                let mut workers: BTreeMap<&str, (u64, f64)> = BTreeMap::new();
+
+                for share in recent {
+                    let (count, weight) = workers.entry(&share.worker).or_default();
+                    *count += 1;
+                    *weight += 16f64.powi(share.share_zeros as i32);
+                }
+
+                let total: f64 = workers.values().map(|(_, weight)| weight).sum();
+
+                // A block with no shares in the window goes to whoever found it
+                if workers.is_empty() {
+                    workers.insert(&block.worker, (0, 1.0));
+                }
+
+                for (worker, (shares, weight)) in workers {
+                    payouts.push(Payout {
+                        index: block.index,
+                        nonce: block.nonce,
+                        worker: worker.to_string(),
+                        shares,
+                        fraction: if total > 0.0 { weight / total } else { 1.0 },
+                    });
+                }
+            }
+        }
+    }
+
+    payouts
+}
+
+pub fn write_csv(out: &mut impl Write, payouts: &[Payout]) -> io::Result<()> {
+    writeln!(out, "index,nonce,worker,shares,fraction")?;
+
+    for payout in payouts {
+        writeln!(
+            out,
+            "{},{},{},{},{:.6}",
+            payout.index,
+            payout.nonce,
+            csv_field(&payout.worker),
+            payout.shares,
+            payout.fraction
+        )?;
+    }
+
+    Ok(())
+}
+
+pub fn write_table(out: &mut impl Write, payouts: &[Payout]) -> io::Result<()> {
+    writeln!(out, "{:>8} {:>20} {:<16} {:>8} {:>8}", "index", "nonce", "worker", "shares", "share")?;
+
+    for payout in payouts {
+        writeln!(
+            out,
+            "{:>8} {:>20} {:<16} {:>8} {:>7.2}%",
+            payout.index,
+            payout.nonce,
+            payout.worker,
+            payout.shares,
+            payout.fraction * 100.0
+        )?;
+    }
+
+    Ok(())
+}
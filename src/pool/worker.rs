@@ -0,0 +1,266 @@
+use std::collections::VecDeque;
+use std::io::{self, BufReader};
+use std::net::TcpStream;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use tracing::{error, info, warn};
+
+use super::{receive, send, Reply, Request, WorkerArgs, INVALID_TOKEN};
+use crate::history;
+use crate::job::Job;
+use crate::miner::{Hit, NonceRange, Outcome};
+use crate::strkey;
+
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+enum Event {
+    Connected(TcpStream),
+    Disconnected,
+    Reply(Reply),
+    Hit(u64, Hit),
+    Finished(u64, NonceRange, u64, Outcome),
+}
+
+struct Lease {
+    job_id: u64,
+    stop: Arc<AtomicBool>,
+}
+
+/// `history` is only read, for the submission outcomes in the metrics.
+pub fn work(args: &WorkerArgs, history: Option<&Path>) {
+    let name = args.name.clone().unwrap_or_else(history::hostname);
+    let miner = crate::start_miner(&args.miner_args, history);
+
+    let (events_tx, events) = mpsc::channel();
+    spawn_connection(args.pool.clone(), name.clone(), args.token.clone(), events_tx.clone());
+
+    let mut writer: Option<TcpStream> = None;
+    // Reports made while disconnected, sent once the pool is back
+    let mut outbox: VecDeque<Request> = VecDeque::new();
+    // Current job and the zeros it takes to report a share
+    let mut job: Option<(u64, Job, usize)> = None;
+    let mut lease: Option<Lease> = None;
+    // The latest search, which may still be winding down after its lease ended
+    let mut search: Option<JoinHandle<()>> = None;
+
+    for event in events {
+        match event {
+            Event::Connected(stream) => {
+                writer = Some(stream);
+                info!("Connected to {} as {}", args.pool, name);
+
+                // Anything queued while offline goes first
+                flush(&mut writer, &mut outbox);
+            }
+            Event::Disconnected => {
+                if writer.take().is_some() {
+                    let state = if lease.is_some() { "finishing the current lease" } else { "idle" };
+                    warn!("Lost connection to {}, {}", args.pool, state);
+                }
+            }
+            Event::Reply(Reply::Welcome) => {}
+            Event::Reply(Reply::Job { job_id, job: next, share_zeros }) => {
+                if job.as_ref().map(|(current, ..)| *current) != Some(job_id) {
+                    if let Some(lease) = lease.take() {
+                        lease.stop.store(true, Ordering::Relaxed);
+                    }
+
+                    let miner_key = strkey::encode(strkey::ACCOUNT, &next.miner);
+                    info!(
+                        job = job_id,
+                        miner = %miner_key,
+                        "Job {}: block {}, difficulty {}",
+                        job_id,
+                        next.index,
+                        next.difficulty
+                    );
+                    miner.stats.begin_job(next.index, next.difficulty);
+                    crate::logging::set_job(job_id, &miner_key);
+                    job = Some((job_id, next, share_zeros));
+                }
+
+                if lease.is_none() {
+                    deliver(&mut writer, &mut outbox, Request::Lease);
+                }
+            }
+            Event::Reply(Reply::Lease { job_id, range }) => {
+                let Some((current, current_job, share_zeros)) = &job else {
+                    continue;
+                };
+
+                if *current != job_id || lease.is_some() {
+                    continue;
+                }
+
+                let stop = Arc::new(AtomicBool::new(false));
+                let hits = events_tx.clone();
+                let finished = events_tx.clone();
+                let miner = miner.clone();
+                let current_job = current_job.clone();
+                let share_zeros = *share_zeros;
+
+                lease = Some(Lease { job_id, stop: stop.clone() });
+                let previous = search.take();
+
+                search = Some(thread::spawn(move || {
+                    // Searches share the miner's threads and counters, so one at a time
+                    if let Some(previous) = previous {
+                        let _ = previous.join();
+                    }
+
+                    let before = miner.stats.snapshot().total();
+                    let on_hit = move |hit| {
+                        let _ = hits.send(Event::Hit(job_id, hit));
+                    };
+
+                    let outcome = miner.search(&current_job, range, share_zeros, &on_hit, &stop);
+                    let hashes = miner.stats.snapshot().total() - before;
+
+                    let _ = finished.send(Event::Finished(job_id, range, hashes, outcome));
+                }));
+            }
+            Event::Reply(Reply::Solved { job_id }) => {
+                if job.as_ref().is_some_and(|(current, ..)| *current == job_id) {
+                    info!(job = job_id, "Job {} solved, waiting for the next one", job_id);
+
+                    if let Some(lease) = lease.take() {
+                        lease.stop.store(true, Ordering::Relaxed);
+                    }
+
+                    job = None;
+                }
+            }
+            Event::Reply(Reply::Rejected { reason }) => {
+                warn!(job = job.as_ref().map(|(current, ..)| *current), "Pool rejected: {}", reason)
+            }
+            Event::Reply(Reply::Error { message }) => {
+                // Retrying with the same token cannot help; anything else, such as the
+                // pool still holding our old connection, clears up on reconnect
+                if message == INVALID_TOKEN {
+                    error!("Pool error: {}", message);
+                    std::process::exit(1);
+                }
+
+                warn!("Pool error: {}", message);
+            }
+            Event::Hit(job_id, hit) => {
+                let Some((current, current_job, _)) = &job else {
+                    continue;
+                };
+
+                if *current != job_id {
+                    continue;
+                }
+
+                // Every hit is a share; the solution itself is also reported on its own
+                let is_solution = current_job.is_solution(hit.zeros);
+                deliver(&mut writer, &mut outbox, Request::Share { job_id, nonce: hit.nonce });
+
+                if is_solution {
+                    info!(
+                        job = job_id,
+                        miner = %strkey::encode(strkey::ACCOUNT, &current_job.miner),
+                        nonce = hit.nonce,
+                        "Found nonce {} ({} zeros)",
+                        hit.nonce,
+                        hit.zeros
+                    );
+                    miner.stats.record_solution();
+                    deliver(&mut writer, &mut outbox, Request::Solution { job_id, nonce: hit.nonce });
+                }
+            }
+            Event::Finished(job_id, range, hashes, outcome) => {
+                if lease.as_ref().is_some_and(|lease| lease.job_id == job_id) {
+                    lease = None;
+                }
+
+                if let Outcome::Exhausted = outcome {
+                    deliver(&mut writer, &mut outbox, Request::Done { job_id, range, hashes });
+
+                    // Offline workers get a fresh lease with the job replayed on reconnect
+                    let current = job.as_ref().is_some_and(|(current, ..)| *current == job_id);
+
+                    if current && lease.is_none() && writer.is_some() {
+                        deliver(&mut writer, &mut outbox, Request::Lease);
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn deliver(writer: &mut Option<TcpStream>, outbox: &mut VecDeque<Request>, request: Request) {
+    outbox.push_back(request);
+    flush(writer, outbox);
+}
+
+fn flush(writer: &mut Option<TcpStream>, outbox: &mut VecDeque<Request>) {
+    while let (Some(stream), Some(request)) = (writer.as_mut(), outbox.front()) {
+        if send(stream, request).is_err() {
+            // The reader notices the broken connection and reconnects
+            *writer = None;
+            break;
+        }
+
+        outbox.pop_front();
+    }
+}
+
+// Keeps a connection to the pool open, handing each new one to the main loop
+fn spawn_connection(pool: String, name: String, token: Option<String>, events: Sender<Event>) {
+    thread::spawn(move || {
+        let mut backoff = MIN_BACKOFF;
+
+        loop {
+            match connect(&pool, &name, token.as_deref(), &events) {
+                Ok(true) => backoff = MIN_BACKOFF,
+                Ok(false) => {}
+                Err(err) => warn!("Pool {}: {}", pool, err),
+            }
+
+            if events.send(Event::Disconnected).is_err() {
+                return;
+            }
+
+            thread::sleep(backoff);
+            backoff = (backoff * 2).min(MAX_BACKOFF);
+        }
+    });
+}
+
+/// `true` if the pool welcomed us before the connection ended.
+fn connect(pool: &str, name: &str, token: Option<&str>, events: &Sender<Event>) -> io::Result<bool> {
+    let mut stream = TcpStream::connect(pool)?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+
+    send(
+        &mut stream,
+        &Request::Hello {
+            worker: name.to_string(),
+            token: token.map(str::to_string),
+        },
+    )?;
+
+    let mut welcomed = false;
+
+    while let Some(reply) = receive(&mut reader)? {
+        if let Reply::Welcome = reply {
+            welcomed = true;
+
+            if events.send(Event::Connected(stream.try_clone()?)).is_err() {
+                break;
+            }
+        }
+
+        if events.send(Event::Reply(reply)).is_err() {
+            break;
+        }
+    }
+
+    Ok(welcomed)
+}
@@ -0,0 +1,693 @@
+//! Soroban RPC for following the FCM contract: new blocks are noticed from
+//! the contract's events (`getEvents` polled with a cursor) and read from its
+//! ledger entries, which are also polled directly whenever events fail.
+//!
+//! Requests go through a [`Transport`], so a [`Replay`] of recorded
+//! responses can stand in for a live node. [`Endpoints`] spreads them over
+//! several nodes, preferring the freshest and failing over on errors.
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine as _;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn};
+
+use crate::error::MinerError;
+use crate::job::{self, Job};
+use crate::network::Network;
+use crate::xdr::{Reader, ScVal};
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+const EVENTS_PAGE: usize = 100;
+// Ledger entries are re-read this often even while events keep flowing
+const REFRESH: Duration = Duration::from_secs(30);
+// A mine moves the contract on to the next block; other events leave it be
+const BLOCK_EVENTS: &[&str] = &["mine"];
+// A failed endpoint is tried last for this long
+const BACKOFF: Duration = Duration::from_secs(30);
+// Weight of the newest sample in the smoothed latency
+const LATENCY_ALPHA: f64 = 0.3;
+
+/// Sends one JSON-RPC request and returns the whole response body.
+pub trait Transport: Send + Sync {
+    fn call(&self, method: &str, params: &Value) -> Result<Value, MinerError>;
+}
+
+impl<T: Transport + ?Sized> Transport for Arc<T> {
+    fn call(&self, method: &str, params: &Value) -> Result<Value, MinerError> {
+        (**self).call(method, params)
+    }
+}
+
+/// JSON-RPC 2.0 over HTTP(S).
+pub struct Http {
+    url: String,
+    agent: ureq::Agent,
+}
+
+impl Http {
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.to_string(),
+            agent: ureq::AgentBuilder::new().timeout(TIMEOUT).build(),
+        }
+    }
+}
+
+impl Transport for Http {
+    fn call(&self, method: &str, params: &Value) -> Result<Value, MinerError> {
+        let request = json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params });
+
+        self.agent
+            .post(&self.url)
+            .send_json(request)
+            // These already name the URL
+            .map_err(|err| MinerError::Rpc(err.to_string()))?
+            .into_json()
+            .map_err(|err| MinerError::Rpc(format!("{}: {}", self.url, err)))
+    }
+}
+
+/// One request and what came back, as [`Recorder`] writes and [`Replay`] reads them.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Exchange {
+    pub method: String,
+    #[serde(default)]
+    pub params: Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response: Option<Value>,
+    /// Set instead of `response` when the request itself failed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Answers each method with its recorded responses in order. Recorded params
+/// must match the request's, so a replay also checks what was asked.
+pub struct Replay {
+    exchanges: Mutex<VecDeque<Exchange>>,
+}
+
+impl Replay {
+    pub fn new(exchanges: Vec<Exchange>) -> Self {
+        Self {
+            exchanges: Mutex::new(exchanges.into()),
+        }
+    }
+
+    /// Read exchanges from a JSON lines file.
+    pub fn load(path: &Path) -> Result<Self, MinerError> {
+        let file = File::open(path).map_err(|err| MinerError::io(path, err))?;
+        let mut exchanges = vec![];
+
+        for (number, line) in BufReader::new(file).lines().enumerate() {
+            let line = line.map_err(|err| MinerError::io(path, err))?;
+
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let exchange = serde_json::from_str(&line).map_err(|err| MinerError::Io {
+                target: format!("{}:{}", path.display(), number + 1),
+                source: err.into(),
+            })?;
+
+            exchanges.push(exchange);
+        }
+
+        Ok(Self::new(exchanges))
+    }
+}
+
+impl Transport for Replay {
+    fn call(&self, method: &str, params: &Value) -> Result<Value, MinerError> {
+        let mut exchanges = self.exchanges.lock().unwrap();
+
+        let Some(position) = exchanges.iter().position(|exchange| exchange.method == method) else {
+            return Err(MinerError::ReplayEnded(method.to_string()));
+        };
+
+        let exchange = exchanges.remove(position).unwrap();
+
+        // Hand-written recordings may leave the params out
+        if !exchange.params.is_null() && exchange.params != *params {
+            let reason = format!("{} sent {}, but the recording has {}", method, params, exchange.params);
+            return Err(MinerError::Rpc(reason));
+        }
+
+        match (exchange.response, exchange.error) {
+            (Some(response), _) => Ok(response),
+            (None, error) => Err(MinerError::Rpc(error.unwrap_or_default())),
+        }
+    }
+}
+
+/// Passes requests on and appends every exchange to a file for [`Replay`].
+pub struct Recorder {
+    inner: Box<dyn Transport>,
+    file: Mutex<File>,
+}
+
+impl Recorder {
+    pub fn new(inner: Box<dyn Transport>, path: &Path) -> Result<Self, MinerError> {
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|err| MinerError::io(path, err))?;
+
+        Ok(Self {
+            inner,
+            file: Mutex::new(file),
+        })
+    }
+}
+
+impl Transport for Recorder {
+    fn call(&self, method: &str, params: &Value) -> Result<Value, MinerError> {
+        let result = self.inner.call(method, params);
+
+        let exchange = Exchange {
+            method: method.to_string(),
+            params: params.clone(),
+            response: result.as_ref().ok().cloned(),
+            error: match &result {
+                Ok(_) => None,
+                Err(MinerError::Rpc(reason)) => Some(reason.clone()),
+                Err(err) => Some(err.to_string()),
+            },
+        };
+
+        if let Ok(line) = serde_json::to_string(&exchange) {
+            let _ = writeln!(self.file.lock().unwrap(), "{}", line);
+        }
+
+        result
+    }
+}
+
+/// How one endpoint has been doing, for metrics and status.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Health {
+    pub url: String,
+    pub requests: u64,
+    pub errors: u64,
+    /// Smoothed response time in seconds.
+    pub latency: f64,
+    /// Newest ledger the endpoint has reported.
+    pub latest_ledger: u32,
+    /// False while backing off after an error.
+    pub up: bool,
+}
+
+struct Endpoint {
+    url: String,
+    transport: Box<dyn Transport>,
+    health: Mutex<Health>,
+    failed_at: Mutex<Option<Instant>>,
+}
+
+impl Endpoint {
+    fn call(&self, method: &str, params: &Value) -> Result<Value, MinerError> {
+        let started = Instant::now();
+        let result = self.transport.call(method, params);
+        let elapsed = started.elapsed().as_secs_f64();
+
+        let mut health = self.health.lock().unwrap();
+        health.requests += 1;
+
+        match &result {
+            Ok(body) => {
+                health.latency = match health.requests {
+                    1 => elapsed,
+                    _ => LATENCY_ALPHA * elapsed + (1.0 - LATENCY_ALPHA) * health.latency,
+                };
+
+                let result = &body["result"];
+
+                if let Some(ledger) = ledger_number(&result["latestLedger"]).or(ledger_number(&result["sequence"])) {
+                    health.latest_ledger = health.latest_ledger.max(ledger);
+                }
+
+                *self.failed_at.lock().unwrap() = None;
+            }
+            Err(_) => {
+                health.errors += 1;
+                *self.failed_at.lock().unwrap() = Some(Instant::now());
+            }
+        }
+
+        result
+    }
+
+    fn backing_off(&self) -> bool {
+        self.failed_at.lock().unwrap().is_some_and(|at| at.elapsed() < BACKOFF)
+    }
+}
+
+/// Several endpoints behind one transport. Requests go to the endpoint with
+/// the newest ledger, the fastest among equals, and move on to the next when
+/// one fails. `sendTransaction` can be raced against all of them.
+pub struct Endpoints {
+    endpoints: Vec<Arc<Endpoint>>,
+    race_send: bool,
+}
+
+impl Endpoints {
+    pub fn new(urls: &[String], race_send: bool) -> Self {
+        let transports = urls
+            .iter()
+            .map(|url| (url.clone(), Box::new(Http::new(url)) as Box<dyn Transport>))
+            .collect();
+
+        Self::with_transports(transports, race_send)
+    }
+
+    /// Endpoints over any transports, e.g. replays standing in for nodes.
+    pub fn with_transports(transports: Vec<(String, Box<dyn Transport>)>, race_send: bool) -> Self {
+        let endpoints = transports
+            .into_iter()
+            .map(|(url, transport)| {
+                Arc::new(Endpoint {
+                    health: Mutex::new(Health {
+                        url: url.clone(),
+                        up: true,
+                        ..Health::default()
+                    }),
+                    url,
+                    transport,
+                    failed_at: Mutex::new(None),
+                })
+            })
+            .collect();
+
+        Self { endpoints, race_send }
+    }
+
+    pub fn health(&self) -> Vec<Health> {
+        self.endpoints
+            .iter()
+            .map(|endpoint| Health {
+                up: !endpoint.backing_off(),
+                ..endpoint.health.lock().unwrap().clone()
+            })
+            .collect()
+    }
+
+    /// Ask every endpoint for its latest ledger, so the ranking stays current
+    /// for endpoints that are not being used.
+    pub fn check(&self) {
+        for endpoint in &self.endpoints {
+            if let Err(err) = endpoint.call("getLatestLedger", &json!({})) {
+                debug!("Health check of {} failed: {}", endpoint.url, err);
+            }
+        }
+    }
+
+    pub fn spawn_health_checks(self: &Arc<Self>, interval: Duration) {
+        let endpoints = self.clone();
+
+        thread::spawn(move || loop {
+            thread::sleep(interval);
+            endpoints.check();
+        });
+    }
+
+    // Up before backing off, then freshest, then fastest
+    fn ranked(&self) -> Vec<Arc<Endpoint>> {
+        let freshest = self.health().iter().map(|health| health.latest_ledger).max().unwrap_or(0);
+
+        let mut ranked: Vec<_> = self
+            .endpoints
+            .iter()
+            .map(|endpoint| {
+                let health = endpoint.health.lock().unwrap().clone();
+                let lag = freshest.saturating_sub(health.latest_ledger);
+                ((endpoint.backing_off(), lag, health.latency), endpoint.clone())
+            })
+            .collect();
+
+        ranked.sort_by(|(a, _), (b, _)| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        ranked.into_iter().map(|(_, endpoint)| endpoint).collect()
+    }
+
+    // Send to every endpoint at once and take the first accepted answer
+    fn race(&self, method: &str, params: &Value) -> Result<Value, MinerError> {
+        let (results, received) = mpsc::channel();
+
+        for endpoint in &self.endpoints {
+            let (endpoint, results) = (endpoint.clone(), results.clone());
+            let (method, params) = (method.to_string(), params.clone());
+
+            thread::spawn(move || {
+                let _ = results.send(endpoint.call(&method, &params));
+            });
+        }
+
+        drop(results);
+
+        let mut last = Err(MinerError::Rpc(String::from("no endpoints configured")));
+
+        for result in received {
+            // A node that rejects or defers the transaction may not speak for the others
+            let status = result.as_ref().ok().and_then(|body| body["result"]["status"].as_str());
+
+            if matches!(status, Some("PENDING" | "DUPLICATE")) {
+                return result;
+            }
+
+            if last.is_err() || result.is_ok() {
+                last = result;
+            }
+        }
+
+        last
+    }
+}
+
+impl Transport for Endpoints {
+    fn call(&self, method: &str, params: &Value) -> Result<Value, MinerError> {
+        if self.race_send && method == "sendTransaction" && self.endpoints.len() > 1 {
+            return self.race(method, params);
+        }
+
+        let mut last = Err(MinerError::Rpc(String::from("no endpoints configured")));
+
+        for endpoint in self.ranked() {
+            // Only a failed request is worth trying elsewhere
+            match endpoint.call(method, params) {
+                Err(MinerError::Rpc(reason)) => {
+                    warn!("{} failed on {}: {}", method, endpoint.url, reason);
+                    last = Err(MinerError::Rpc(reason));
+                }
+                result => return result,
+            }
+        }
+
+        last
+    }
+}
+
+/// Typed Soroban RPC calls over any transport.
+pub struct Client {
+    transport: Box<dyn Transport>,
+}
+
+/// A page of contract events and where the next page starts.
+pub struct Events {
+    pub events: Vec<Value>,
+    pub cursor: Option<String>,
+    pub latest_ledger: u32,
+}
+
+impl Client {
+    pub fn new(transport: Box<dyn Transport>) -> Self {
+        Self { transport }
+    }
+
+    /// The `result` of a request, or its JSON-RPC error.
+    pub fn call(&self, method: &str, params: Value) -> Result<Value, MinerError> {
+        let mut response = self.transport.call(method, &params)?;
+
+        if let Some(error) = response.get("error").filter(|error| !error.is_null()) {
+            let message = error["message"].as_str().unwrap_or("unknown error");
+            return Err(MinerError::Rpc(format!("{}: {}", method, message)));
+        }
+
+        match response.get_mut("result") {
+            Some(result) => Ok(result.take()),
+            None => Err(MinerError::Rpc(format!("{}: response has no result", method))),
+        }
+    }
+
+    /// Submit a signed transaction envelope (base64 XDR).
+    pub fn send_transaction(&self, envelope: &str) -> Result<Value, MinerError> {
+        self.call("sendTransaction", json!({ "transaction": envelope }))
+    }
+
+    pub fn latest_ledger(&self) -> Result<u32, MinerError> {
+        let result = self.call("getLatestLedger", json!({}))?;
+
+        ledger_number(&result["sequence"]).ok_or_else(|| MinerError::Rpc(String::from("getLatestLedger: no sequence")))
+    }
+
+    /// Events of `contract` after `cursor`, or from `start_ledger` without one.
+    pub fn events(&self, contract: &str, start_ledger: u32, cursor: Option<&str>) -> Result<Events, MinerError> {
+        let filters = json!([{ "type": "contract", "contractIds": [contract] }]);
+
+        // The cursor already says where to start, and the two may not be combined
+        let params = match cursor {
+            Some(cursor) => json!({ "filters": filters, "pagination": { "cursor": cursor, "limit": EVENTS_PAGE } }),
+            None => json!({ "startLedger": start_ledger, "filters": filters, "pagination": { "limit": EVENTS_PAGE } }),
+        };
+
+        let mut result = self.call("getEvents", params)?;
+        let events = match result["events"].take() {
+            Value::Array(events) => events,
+            _ => vec![],
+        };
+
+        // Older nodes only give each event's paging token
+        let cursor = result["cursor"]
+            .as_str()
+            .filter(|cursor| !cursor.is_empty())
+            .or_else(|| events.last().and_then(|event| event["pagingToken"].as_str()))
+            .map(str::to_string);
+
+        Ok(Events {
+            events,
+            cursor,
+            latest_ledger: ledger_number(&result["latestLedger"]).unwrap_or(start_ledger),
+        })
+    }
+
+    /// The value of each contract data entry in `keys`, `None` where missing.
+    pub fn contract_data(&self, keys: &[Vec<u8>]) -> Result<Vec<Option<ScVal>>, MinerError> {
+        let encoded: Vec<String> = keys.iter().map(|key| BASE64.encode(key)).collect();
+        let result = self.call("getLedgerEntries", json!({ "keys": encoded }))?;
+        let entries = result["entries"].as_array().cloned().unwrap_or_default();
+
+        encoded
+            .iter()
+            .map(|key| {
+                let Some(entry) = entries.iter().find(|entry| entry["key"].as_str() == Some(key)) else {
+                    return Ok(None);
+                };
+
+                let data = entry["xdr"]
+                    .as_str()
+                    .and_then(|data| BASE64.decode(data).ok())
+                    .ok_or_else(|| MinerError::Rpc(String::from("getLedgerEntries: entry without XDR")))?;
+
+                Reader::new(&data)
+                    .contract_data()
+                    .map(Some)
+                    .map_err(|reason| MinerError::Rpc(format!("getLedgerEntries: {}", reason)))
+            })
+            .collect()
+    }
+}
+
+// Ledger numbers arrive as numbers or, from some nodes, strings
+fn ledger_number(value: &Value) -> Option<u32> {
+    match value {
+        Value::Number(number) => number.as_u64().and_then(|n| u32::try_from(n).ok()),
+        Value::String(text) => text.parse().ok(),
+        _ => None,
+    }
+}
+
+/// The block to mine next: one past the contract's current block.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Block {
+    pub index: u64,
+    pub prev_hash: [u8; 32],
+    pub difficulty: usize,
+}
+
+impl Block {
+    pub fn job(&self, miner: [u8; 32], message: String) -> Job {
+        Job {
+            index: self.index,
+            prev_hash: self.prev_hash,
+            difficulty: self.difficulty,
+            miner,
+            message,
+        }
+    }
+
+    /// `{"index", "prev_hash", "difficulty"}`, as the pool and daemon take jobs.
+    pub fn to_json(&self) -> Value {
+        json!({
+            "index": self.index,
+            "prev_hash": hex::encode(self.prev_hash),
+            "difficulty": self.difficulty,
+        })
+    }
+}
+
+/// Follows the contract and reports each new block once.
+pub struct Watcher {
+    client: Client,
+    network: Network,
+    contract_id: String,
+    cursor: Option<String>,
+    start_ledger: Option<u32>,
+    last: Option<Block>,
+    refreshed: Option<Instant>,
+    // Events failed last time, so blocks are found by polling entries
+    degraded: bool,
+}
+
+impl Watcher {
+    pub fn new(client: Client, network: Network) -> Result<Self, MinerError> {
+        network.contract()?;
+
+        Ok(Self {
+            client,
+            contract_id: network.contract_id.clone().unwrap_or_default(),
+            network,
+            cursor: None,
+            start_ledger: None,
+            last: None,
+            refreshed: None,
+            degraded: false,
+        })
+    }
+
+    /// Read the current block from the contract's ledger entries.
+    pub fn current_block(&self) -> Result<Block, MinerError> {
+        let instance = self.client.contract_data(&[self.network.instance_key()?])?.remove(0);
+        let Some(ScVal::Instance(storage)) = instance else {
+            return Err(MinerError::Rpc(format!("contract {} has no instance entry", self.contract_id)));
+        };
+
+        // The farm state is the stored map with the current index and difficulty
+        let state = storage
+            .iter()
+            .map(|(_, value)| value)
+            .find(|value| value.get("current").is_some() && value.get("difficulty").is_some())
+            .ok_or_else(|| MinerError::Rpc(String::from("contract instance holds no block state")))?;
+
+        let current = state.get("current").and_then(ScVal::as_u64).unwrap_or_default();
+        let difficulty = state.get("difficulty").and_then(ScVal::as_u64).unwrap_or_default();
+
+        let block = self.client.contract_data(&[self.network.block_key(current)?])?.remove(0);
+        let prev_hash = match block.as_ref().and_then(|block| block.get("hash")) {
+            Some(ScVal::Bytes(hash)) => job::parse_hash("hash", &hex::encode(hash))?,
+            _ => return Err(MinerError::Rpc(format!("block {} has no hash entry", current))),
+        };
+
+        Ok(Block {
+            index: current + 1,
+            prev_hash,
+            difficulty: job::check_difficulty(difficulty as usize)?,
+        })
+    }
+
+    /// Whether the contract emitted a block event since the last call.
+    fn new_events(&mut self) -> Result<bool, MinerError> {
+        let start_ledger = match self.start_ledger {
+            Some(ledger) => ledger,
+            None => self.client.latest_ledger()?,
+        };
+
+        let page = self.client.events(&self.contract_id, start_ledger, self.cursor.as_deref())?;
+        let mut blocks = false;
+
+        for event in &page.events {
+            let name = event["topic"][0]
+                .as_str()
+                .and_then(|topic| BASE64.decode(topic).ok())
+                .and_then(|topic| Reader::new(&topic).scval().ok());
+
+            match name {
+                Some(ScVal::Symbol(name)) => {
+                    debug!("Contract event {} in ledger {}", name, event["ledger"]);
+                    blocks |= BLOCK_EVENTS.contains(&name.as_str());
+                }
+                _ => debug!("Contract event in ledger {}", event["ledger"]),
+            }
+        }
+
+        self.start_ledger = Some(page.latest_ledger.max(start_ledger));
+        self.cursor = page.cursor.or(self.cursor.take());
+
+        Ok(blocks)
+    }
+
+    /// One round: check for events, falling back to reading the ledger
+    /// entries, and return the block if it changed.
+    pub fn poll(&mut self) -> Result<Option<Block>, MinerError> {
+        let stale = self.refreshed.is_none_or(|at| at.elapsed() >= REFRESH);
+
+        let changed = match self.new_events() {
+            Ok(events) => {
+                if self.degraded {
+                    info!("getEvents works again, following contract events");
+                    self.degraded = false;
+                }
+
+                events || stale
+            }
+            Err(err @ MinerError::Rpc(_)) => {
+                if !self.degraded {
+                    warn!("getEvents failed, polling ledger entries instead: {}", err);
+                    self.degraded = true;
+                }
+
+                // The cursor may have aged out of the node's retention window
+                self.cursor = None;
+                self.start_ledger = None;
+                true
+            }
+            Err(err) => return Err(err),
+        };
+
+        if !changed {
+            return Ok(None);
+        }
+
+        let block = self.current_block()?;
+        self.refreshed = Some(Instant::now());
+
+        if self.last.as_ref() == Some(&block) {
+            return Ok(None);
+        }
+
+        self.last = Some(block.clone());
+
+        Ok(Some(block))
+    }
+
+    /// Poll every `interval` until `stop`, handing each new block to `on_block`.
+    /// RPC failures are logged and retried; other errors end the watch.
+    pub fn run(
+        &mut self,
+        interval: Duration,
+        stop: &AtomicBool,
+        mut on_block: impl FnMut(&Block),
+    ) -> Result<(), MinerError> {
+        while !stop.load(Ordering::Relaxed) {
+            let started = Instant::now();
+
+            match self.poll() {
+                Ok(Some(block)) => on_block(&block),
+                Ok(None) => {}
+                Err(err @ MinerError::Rpc(_)) => warn!("Reading the contract failed: {}", err),
+                Err(err) => return Err(err),
+            }
+
+            thread::sleep(interval.saturating_sub(started.elapsed()));
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,217 @@
+//! A search running on its own worker threads, reporting through a callback.
+//! The async API and the C ABI are thin wrappers around this.
+
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::engine::{self, Engine};
+use crate::job::Job;
+use crate::miner::{Hit, Miner, NonceRange, Order, Outcome, DEFAULT_BATCH_SIZE};
+use crate::stats::Stats;
+use crate::throttle::Throttle;
+
+// Granularity of the hashrate reporter's sleep, bounding how long it outlives the search
+const POLL: Duration = Duration::from_millis(100);
+
+#[derive(Clone)]
+pub struct MineOptions {
+    /// Worker threads, all CPUs when `None`.
+    pub threads: Option<usize>,
+    pub engine: Engine,
+    /// Nonces per batch, at least 2.
+    pub batch_size: u64,
+    pub order: Order,
+    pub seed: u64,
+    pub range: NonceRange,
+    /// Percent of each worker's time spent hashing.
+    pub cpu_percent: u32,
+    /// How often to send [`MinerEvent::Hashrate`].
+    pub report_interval: Duration,
+}
+
+impl Default for MineOptions {
+    fn default() -> Self {
+        Self {
+            threads: None,
+            engine: Engine::default(),
+            batch_size: DEFAULT_BATCH_SIZE,
+            order: Order::Asc,
+            seed: 0,
+            range: NonceRange::FULL,
+            cpu_percent: 100,
+            report_interval: Duration::from_secs(2),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Solution {
+    pub nonce: u64,
+    pub hash: [u8; 32],
+    pub zeros: usize,
+    /// Hashes tried by the time it was found.
+    pub hashes: u64,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum MinerEvent {
+    /// Hashes so far and the rate since the last report, in H/s.
+    Hashrate { hashes: u64, rate: f64 },
+    /// A hash with more zeros than any before it, picked up after each batch.
+    Best { nonce: u64, hash: [u8; 32], zeros: usize },
+    Solution(Solution),
+    /// The whole range was searched without a solution.
+    Exhausted { hashes: u64 },
+}
+
+/// Handle to a running search. Dropping it does not stop the workers; call
+/// [`Session::cancel`] for that.
+pub struct Session {
+    stop: Arc<AtomicBool>,
+    finished: Arc<AtomicBool>,
+}
+
+impl Session {
+    /// Run the self-test, then search until the first solution, the end of the
+    /// range or [`Session::cancel`]. `sink` is dropped once every event is sent.
+    pub fn start(
+        job: Job,
+        opts: MineOptions,
+        sink: impl Fn(MinerEvent) + Send + Sync + 'static,
+    ) -> Result<Self, String> {
+        if opts.batch_size < 2 {
+            return Err(format!("batch size {} is below 2", opts.batch_size));
+        }
+
+        engine::self_test()?;
+
+        let threads = opts.threads.unwrap_or_else(num_cpus::get).max(1);
+        let miner = Arc::new(Miner {
+            threads,
+            batch_size: opts.batch_size,
+            engine: opts.engine,
+            order: opts.order,
+            seed: opts.seed,
+            throttle: Arc::new(Throttle::new(threads, opts.cpu_percent.clamp(1, 100), 0, false)),
+            stats: Arc::new(Stats::new(threads)),
+        });
+
+        let sink = Arc::new(sink);
+        let stop = Arc::new(AtomicBool::new(false));
+        let finished = Arc::new(AtomicBool::new(false));
+        // Zeros of the last best sent
+        let reported = Arc::new(AtomicUsize::new(0));
+
+        // Only solutions come through `on_hit`; bests are read from the stats
+        // the workers publish after every batch, so the hot loop stays uncontended
+        let search = miner.spawn(
+            job.clone(),
+            opts.range,
+            job.difficulty,
+            on_hit(job.clone(), miner.clone(), sink.clone(), stop.clone(), reported.clone()),
+            stop.clone(),
+        );
+
+        spawn_reporter(job, miner.clone(), sink.clone(), finished.clone(), reported, opts.report_interval);
+
+        thread::spawn({
+            let stop = stop.clone();
+            let finished = finished.clone();
+
+            move || {
+                if let Ok(Outcome::Exhausted) = search.join() {
+                    sink(MinerEvent::Exhausted {
+                        hashes: miner.stats.snapshot().total(),
+                    });
+                }
+
+                stop.store(true, Ordering::Relaxed);
+                finished.store(true, Ordering::Relaxed);
+            }
+        });
+
+        Ok(Self { stop, finished })
+    }
+
+    /// Ask the workers to stop after their current batch. Safe from any thread.
+    pub fn cancel(&self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_stopping(&self) -> bool {
+        self.stop.load(Ordering::Relaxed)
+    }
+
+    /// Whether the workers have all returned.
+    pub fn is_finished(&self) -> bool {
+        self.finished.load(Ordering::Relaxed)
+    }
+}
+
+// Forward the first solution, as a best too if the reporter has not seen it
+// yet, then stop the workers
+fn on_hit(
+    job: Job,
+    miner: Arc<Miner>,
+    sink: Arc<impl Fn(MinerEvent) + Send + Sync + 'static>,
+    stop: Arc<AtomicBool>,
+    reported: Arc<AtomicUsize>,
+) -> impl Fn(Hit) + Send + Sync + 'static {
+    move |hit| {
+        if !job.is_solution(hit.zeros) || stop.swap(true, Ordering::Relaxed) {
+            return;
+        }
+
+        if hit.zeros > reported.fetch_max(hit.zeros, Ordering::Relaxed) {
+            sink(MinerEvent::Best {
+                nonce: hit.nonce,
+                hash: hit.hash,
+                zeros: hit.zeros,
+            });
+        }
+
+        sink(MinerEvent::Solution(Solution {
+            nonce: hit.nonce,
+            hash: hit.hash,
+            zeros: hit.zeros,
+            hashes: miner.stats.snapshot().total(),
+        }));
+    }
+}
+
+// Send new bests as the workers publish them, and the hashrate every `interval`
+fn spawn_reporter(
+    job: Job,
+    miner: Arc<Miner>,
+    sink: Arc<impl Fn(MinerEvent) + Send + Sync + 'static>,
+    finished: Arc<AtomicBool>,
+    reported: Arc<AtomicUsize>,
+    interval: Duration,
+) {
+    thread::spawn(move || {
+        let mut last = (Instant::now(), 0);
+
+        while !finished.load(Ordering::Relaxed) {
+            let mut slept = Duration::ZERO;
+
+            while slept < interval && !finished.load(Ordering::Relaxed) {
+                thread::sleep(POLL);
+                slept += POLL;
+
+                if let Some((zeros, nonce)) = miner.stats.best() {
+                    if zeros > reported.fetch_max(zeros, Ordering::Relaxed) {
+                        sink(MinerEvent::Best { nonce, hash: job.hash(nonce), zeros });
+                    }
+                }
+            }
+
+            let hashes = miner.stats.snapshot().total();
+            let rate = (hashes - last.1) as f64 / last.0.elapsed().as_secs_f64();
+            last = (Instant::now(), hashes);
+
+            sink(MinerEvent::Hashrate { hashes, rate });
+        }
+    });
+}
@@ -0,0 +1,285 @@
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::info;
+
+use crate::estimate;
+use crate::rpc::{Endpoints, Health};
+
+const REPORT_INTERVAL: Duration = Duration::from_secs(2);
+
+// Weight of the newest sample in the smoothed rate
+const EMA_ALPHA: f64 = 0.3;
+
+/// Counters owned by a single worker, padded so no two workers share a cache line.
+#[repr(align(128))]
+#[derive(Default)]
+pub struct WorkerSlot {
+    hashes: AtomicU64,
+    last_nonce: AtomicU64,
+    // Best hash of the current job; the nonce is stored first so a reader that
+    // sees the zeros also sees their nonce
+    best_zeros: AtomicUsize,
+    best_nonce: AtomicU64,
+}
+
+impl WorkerSlot {
+    pub fn hashes(&self) -> u64 {
+        self.hashes.load(Ordering::Relaxed)
+    }
+
+    /// Publish the worker's running total; called once per batch.
+    #[inline]
+    pub fn record(&self, hashes: u64, last_nonce: u64) {
+        self.hashes.store(hashes, Ordering::Relaxed);
+        self.last_nonce.store(last_nonce, Ordering::Relaxed);
+    }
+
+    /// Publish the worker's best hash if it beats its last one; while mining
+    /// only the owning worker writes the slot, so this never contends.
+    #[inline]
+    pub fn record_best(&self, zeros: usize, nonce: u64) {
+        if zeros > self.best_zeros.load(Ordering::Relaxed) {
+            self.best_nonce.store(nonce, Ordering::Relaxed);
+            self.best_zeros.store(zeros, Ordering::Release);
+        }
+    }
+
+    fn best(&self) -> (usize, u64) {
+        let zeros = self.best_zeros.load(Ordering::Acquire);
+        (zeros, self.best_nonce.load(Ordering::Relaxed))
+    }
+}
+
+pub struct Stats {
+    slots: Box<[WorkerSlot]>,
+    difficulty: AtomicUsize,
+    job_index: AtomicU64,
+    solutions: AtomicU64,
+    created: Instant,
+    // When the current job arrived, in nanoseconds since `created`; zero once
+    // workers have started on it
+    switch_started: AtomicU64,
+    switch_latency: AtomicU64,
+    rates: Mutex<Rates>,
+    // RPC endpoints the current jobs come from, if any
+    rpc: Mutex<Option<Arc<Endpoints>>>,
+}
+
+pub struct Snapshot {
+    pub at: Instant,
+    pub hashes: Vec<u64>,
+    pub last_nonces: Vec<u64>,
+}
+
+impl Snapshot {
+    pub fn total(&self) -> u64 {
+        self.hashes.iter().sum()
+    }
+}
+
+impl Stats {
+    pub fn new(threads: usize) -> Self {
+        Self {
+            slots: (0..threads).map(|_| WorkerSlot::default()).collect(),
+            difficulty: AtomicUsize::new(0),
+            job_index: AtomicU64::new(0),
+            solutions: AtomicU64::new(0),
+            created: Instant::now(),
+            switch_started: AtomicU64::new(0),
+            switch_latency: AtomicU64::new(0),
+            rates: Mutex::new(Rates::default()),
+            rpc: Mutex::new(None),
+        }
+    }
+
+    /// A new job arrived; its difficulty drives the ETA.
+    pub fn begin_job(&self, index: u64, difficulty: usize) {
+        self.job_index.store(index, Ordering::Relaxed);
+        self.difficulty.store(difficulty, Ordering::Relaxed);
+
+        for slot in self.slots.iter() {
+            slot.best_zeros.store(0, Ordering::Relaxed);
+        }
+
+        self.switch_started
+            .store(self.created.elapsed().as_nanos().max(1) as u64, Ordering::Relaxed);
+    }
+
+    /// Workers started hashing; closes the job switch if one is pending.
+    pub fn job_started(&self) {
+        let started = self.switch_started.swap(0, Ordering::Relaxed);
+
+        if started != 0 {
+            let now = self.created.elapsed().as_nanos() as u64;
+            self.switch_latency.store(now.saturating_sub(started), Ordering::Relaxed);
+        }
+    }
+
+    pub fn record_solution(&self) {
+        self.solutions.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn job_index(&self) -> u64 {
+        self.job_index.load(Ordering::Relaxed)
+    }
+
+    pub fn difficulty(&self) -> usize {
+        self.difficulty.load(Ordering::Relaxed)
+    }
+
+    /// Most leading zeros seen since the job began.
+    pub fn best_zeros(&self) -> usize {
+        self.best().map_or(0, |(zeros, _)| zeros)
+    }
+
+    /// Zeros and nonce of the best hash since the job began, if any was hashed,
+    /// taken across the workers' slots.
+    pub fn best(&self) -> Option<(usize, u64)> {
+        self.slots.iter().map(WorkerSlot::best).filter(|best| best.0 > 0).max_by_key(|best| best.0)
+    }
+
+    pub fn solutions(&self) -> u64 {
+        self.solutions.load(Ordering::Relaxed)
+    }
+
+    /// Time from the last job arriving to workers hashing it.
+    pub fn switch_latency(&self) -> Duration {
+        Duration::from_nanos(self.switch_latency.load(Ordering::Relaxed))
+    }
+
+    /// Rates as of the reporter's last tick.
+    pub fn rates(&self) -> Rates {
+        self.rates.lock().unwrap().clone()
+    }
+
+    /// Report the health of these endpoints alongside the hashing stats.
+    pub fn set_rpc(&self, endpoints: Arc<Endpoints>) {
+        *self.rpc.lock().unwrap() = Some(endpoints);
+    }
+
+    pub fn rpc_health(&self) -> Vec<Health> {
+        self.rpc.lock().unwrap().as_ref().map(|endpoints| endpoints.health()).unwrap_or_default()
+    }
+
+    pub fn slot(&self, thread_id: usize) -> &WorkerSlot {
+        &self.slots[thread_id]
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            at: Instant::now(),
+            hashes: self
+                .slots
+                .iter()
+                .map(|slot| slot.hashes.load(Ordering::Relaxed))
+                .collect(),
+            last_nonces: self
+                .slots
+                .iter()
+                .map(|slot| slot.last_nonce.load(Ordering::Relaxed))
+                .collect(),
+        }
+    }
+
+    /// Update rates every few seconds and, if `print`, show them with the ETA for
+    /// the current difficulty and the chance of solving within `window` seconds.
+    pub fn spawn_reporter(self: &Arc<Self>, window: u64, print: bool) {
+        let stats = self.clone();
+
+        thread::spawn(move || {
+            let mut rates = Rates::default();
+            let mut last = stats.snapshot();
+
+            loop {
+                thread::sleep(REPORT_INTERVAL);
+
+                let now = stats.snapshot();
+                rates.update(&last, &now);
+                last = now;
+
+                *stats.rates.lock().unwrap() = rates.clone();
+
+                if !print {
+                    continue;
+                }
+
+                let zeros = stats.difficulty.load(Ordering::Relaxed);
+
+                info!(
+                    job = stats.job_index(),
+                    "{}, ETA {} ({:.0}% within {})",
+                    rates,
+                    estimate::format_duration(estimate::eta(zeros, rates.ema)),
+                    estimate::solve_chance(zeros, rates.ema, window as f64) * 100.0,
+                    estimate::format_duration(window as f64)
+                );
+            }
+        });
+    }
+}
+
+/// Rates derived from two snapshots, in H/s.
+#[derive(Clone, Default)]
+pub struct Rates {
+    pub total: f64,
+    pub ema: f64,
+    pub per_thread: Vec<f64>,
+    pub nonce: u64,
+}
+
+impl Rates {
+    pub fn update(&mut self, last: &Snapshot, now: &Snapshot) {
+        let elapsed = now.at.duration_since(last.at).as_secs_f64().max(1e-3);
+
+        self.per_thread = now
+            .hashes
+            .iter()
+            .zip(&last.hashes)
+            .map(|(now, last)| now.saturating_sub(*last) as f64 / elapsed)
+            .collect();
+        self.total = self.per_thread.iter().sum();
+        self.nonce = now.last_nonces.iter().copied().max().unwrap_or(0);
+
+        self.ema = if self.ema == 0.0 {
+            self.total
+        } else {
+            EMA_ALPHA * self.total + (1.0 - EMA_ALPHA) * self.ema
+        };
+    }
+
+    pub fn min_thread(&self) -> f64 {
+        self.per_thread.iter().copied().fold(f64::INFINITY, f64::min)
+    }
+
+    pub fn max_thread(&self) -> f64 {
+        self.per_thread.iter().copied().fold(0.0, f64::max)
+    }
+
+    /// Spread between the slowest and fastest worker relative to the mean.
+    pub fn imbalance(&self) -> f64 {
+        let mean = self.total / self.per_thread.len() as f64;
+
+        if mean > 0.0 {
+            (self.max_thread() - self.min_thread()) / mean
+        } else {
+            0.0
+        }
+    }
+}
+
+impl std::fmt::Display for Rates {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Hashrate: {:.2} MH/s (ema {:.2} MH/s, per thread {:.2}-{:.2} MH/s, imbalance {:.1}%), nonce {}",
+            self.total / 1_000_000.0,
+            self.ema / 1_000_000.0,
+            self.min_thread() / 1_000_000.0,
+            self.max_thread() / 1_000_000.0,
+            self.imbalance() * 100.0,
+            self.nonce
+        )
+    }
+}
@@ -0,0 +1,104 @@
+// Stellar strkeys: base32 of a version byte, the payload and a CRC16-XModem
+// checksum (little endian)
+
+const ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+pub const ACCOUNT: u8 = 6 << 3; // G...
+pub const CONTRACT: u8 = 2 << 3; // C...
+
+pub fn encode(version: u8, payload: &[u8; 32]) -> String {
+    let mut data = Vec::with_capacity(35);
+    data.push(version);
+    data.extend_from_slice(payload);
+    data.extend_from_slice(&crc16(&data).to_le_bytes());
+
+    let mut out = String::with_capacity(56);
+    let mut buffer = 0u32;
+    let mut bits = 0;
+
+    for byte in data {
+        buffer = (buffer << 8) | byte as u32;
+        bits += 8;
+
+        while bits >= 5 {
+            bits -= 5;
+            out.push(ALPHABET[(buffer >> bits) as usize & 31] as char);
+        }
+    }
+
+    if bits > 0 {
+        out.push(ALPHABET[(buffer << (5 - bits)) as usize & 31] as char);
+    }
+
+    out
+}
+
+pub fn decode(version: u8, key: &str) -> Result<[u8; 32], String> {
+    if key.len() != 56 {
+        return Err(format!("strkey must be 56 characters, got {}", key.len()));
+    }
+
+    let mut data = Vec::with_capacity(35);
+    let mut buffer = 0u32;
+    let mut bits = 0;
+
+    for c in key.bytes() {
+        let value = ALPHABET
+            .iter()
+            .position(|&a| a == c)
+            .ok_or_else(|| format!("invalid strkey character {:?}", c as char))?;
+
+        buffer = (buffer << 5) | value as u32;
+        bits += 5;
+
+        if bits >= 8 {
+            bits -= 8;
+            data.push((buffer >> bits) as u8);
+        }
+    }
+
+    if data[0] != version {
+        return Err(String::from("strkey has the wrong version byte"));
+    }
+
+    let checksum = u16::from_le_bytes([data[33], data[34]]);
+
+    if crc16(&data[..33]) != checksum {
+        return Err(String::from("strkey checksum mismatch"));
+    }
+
+    Ok(data[1..33].try_into().unwrap())
+}
+
+fn crc16(data: &[u8]) -> u16 {
+    let mut crc = 0u16;
+
+    for &byte in data {
+        crc ^= (byte as u16) << 8;
+
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+
+    crc
+}
+
+/// Serde helpers for account ids stored as `G...` strings.
+pub mod account {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(key: &[u8; 32], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&super::encode(super::ACCOUNT, key))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<[u8; 32], D::Error> {
+        let key = String::deserialize(deserializer)?;
+
+        super::decode(super::ACCOUNT, &key).map_err(serde::de::Error::custom)
+    }
+}
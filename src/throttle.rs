@@ -0,0 +1,237 @@
+use std::fs;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use tracing::warn;
+
+const IDLE_POLL: Duration = Duration::from_secs(1);
+const PAUSE_SLICE: Duration = Duration::from_millis(100);
+
+// Percent of the whole machine kept free for other processes in idle mode
+const IDLE_HEADROOM: u32 = 10;
+// How fast idle mode gives CPU back to the workers once the machine is quiet
+const IDLE_RAMP_UP: u32 = 10;
+
+/// Per-batch duty cycle shared by all workers.
+///
+/// Workers call `pace` after every batch; it sleeps long enough to keep each
+/// worker under the CPU percentage and the total under the hashrate cap.
+pub struct Throttle {
+    cpu_percent: AtomicU32,
+    max_hashrate: AtomicU64, // H/s across all workers, 0 = uncapped
+    idle: AtomicBool,
+    idle_limit: AtomicU32, // percent the idle monitor currently allows
+    paused: AtomicBool,
+    active: AtomicUsize, // workers below this index hash, the rest sit parked
+    threads: usize,
+}
+
+impl Throttle {
+    pub fn new(threads: usize, cpu_percent: u32, max_hashrate: u64, idle: bool) -> Self {
+        Self {
+            cpu_percent: AtomicU32::new(cpu_percent.clamp(1, 100)),
+            max_hashrate: AtomicU64::new(max_hashrate),
+            idle: AtomicBool::new(idle),
+            idle_limit: AtomicU32::new(100),
+            paused: AtomicBool::new(false),
+            active: AtomicUsize::new(threads),
+            threads,
+        }
+    }
+
+    pub fn set_cpu_percent(&self, percent: u32) {
+        self.cpu_percent.store(percent.clamp(1, 100), Ordering::Relaxed);
+    }
+
+    pub fn cpu_percent(&self) -> u32 {
+        self.cpu_percent.load(Ordering::Relaxed)
+    }
+
+    pub fn set_max_hashrate(&self, hashrate: u64) {
+        self.max_hashrate.store(hashrate, Ordering::Relaxed);
+    }
+
+    /// H/s across all workers, 0 when uncapped.
+    pub fn max_hashrate(&self) -> u64 {
+        self.max_hashrate.load(Ordering::Relaxed)
+    }
+
+    pub fn set_idle(&self, idle: bool) {
+        self.idle.store(idle, Ordering::Relaxed);
+    }
+
+    pub fn is_idle(&self) -> bool {
+        self.idle.load(Ordering::Relaxed)
+    }
+
+    pub fn set_paused(&self, paused: bool) {
+        self.paused.store(paused, Ordering::Relaxed);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+
+    /// Park every worker from `threads` up. Parked workers keep their share of
+    /// the range and pick it up again once raised.
+    pub fn set_active_threads(&self, threads: usize) {
+        self.active.store(threads.clamp(1, self.threads), Ordering::Relaxed);
+    }
+
+    pub fn active_threads(&self) -> usize {
+        self.active.load(Ordering::Relaxed)
+    }
+
+    /// Percent of a worker's time spent hashing, 0 meaning paused.
+    fn duty_percent(&self, worker: usize) -> u32 {
+        if self.paused.load(Ordering::Relaxed) || worker >= self.active.load(Ordering::Relaxed) {
+            return 0;
+        }
+
+        let percent = self.cpu_percent.load(Ordering::Relaxed);
+
+        if self.idle.load(Ordering::Relaxed) {
+            percent.min(self.idle_limit.load(Ordering::Relaxed))
+        } else {
+            percent
+        }
+    }
+
+    /// Sleep after a batch of `hashes` that kept `worker` busy for `busy`.
+    /// Returns early once `stop` is set, even while paused.
+    #[inline]
+    pub fn pace(&self, worker: usize, busy: Duration, hashes: u64, stop: &AtomicBool) {
+        let mut percent = self.duty_percent(worker);
+
+        while percent == 0 {
+            if stop.load(Ordering::Relaxed) {
+                return;
+            }
+
+            thread::sleep(PAUSE_SLICE);
+            percent = self.duty_percent(worker);
+        }
+
+        let mut pause = Duration::ZERO;
+
+        if percent < 100 {
+            pause = busy * (100 - percent) / percent;
+        }
+
+        let max_hashrate = self.max_hashrate.load(Ordering::Relaxed);
+
+        if max_hashrate > 0 {
+            let active = self.active.load(Ordering::Relaxed);
+            let per_thread = (max_hashrate as f64 / active as f64).max(1.0);
+            let min_batch = Duration::from_secs_f64(hashes as f64 / per_thread);
+
+            pause = pause.max(min_batch.saturating_sub(busy));
+        }
+
+        if !pause.is_zero() {
+            thread::sleep(pause);
+        }
+    }
+
+    /// Watch `/proc/stat` and `/proc/loadavg` and shrink the duty cycle while
+    /// other processes want the CPU. Only has an effect while idle mode is on.
+    pub fn spawn_idle_monitor(self: &Arc<Self>) {
+        let throttle = self.clone();
+
+        thread::spawn(move || {
+            let cpus = num_cpus::get() as u64;
+            let mut last = match CpuTimes::read() {
+                Some(times) => times,
+                None => {
+                    warn!("Idle mode needs /proc/stat, mining without it");
+                    return;
+                }
+            };
+
+            loop {
+                thread::sleep(IDLE_POLL);
+
+                let Some(now) = CpuTimes::read() else {
+                    continue;
+                };
+
+                if !throttle.idle.load(Ordering::Relaxed) {
+                    throttle.idle_limit.store(100, Ordering::Relaxed);
+                    last = now;
+                    continue;
+                }
+
+                let total = now.total.saturating_sub(last.total).max(1);
+                let busy = now.busy.saturating_sub(last.busy);
+                let own = now.own.saturating_sub(last.own);
+                let others = (busy.saturating_sub(own) * 100 / total) as u32;
+                last = now;
+
+                // Whole-machine share left for us, spread over our workers
+                let free = 100u32.saturating_sub(others + IDLE_HEADROOM) as u64;
+                let mut allowed = (free * cpus / throttle.threads as u64).min(100) as u32;
+
+                // Runnable tasks beyond the CPUs we leave free are queued behind us
+                if let Some(running) = running_tasks() {
+                    let ours = throttle.threads as u64 + 1;
+                    let spare = cpus.saturating_sub(throttle.threads as u64);
+
+                    if running.saturating_sub(ours) > spare {
+                        allowed /= 2;
+                    }
+                }
+
+                let previous = throttle.idle_limit.load(Ordering::Relaxed);
+                let limit = allowed.min(previous + IDLE_RAMP_UP);
+
+                throttle.idle_limit.store(limit, Ordering::Relaxed);
+            }
+        });
+    }
+}
+
+struct CpuTimes {
+    total: u64,
+    busy: u64,
+    own: u64,
+}
+
+impl CpuTimes {
+    fn read() -> Option<Self> {
+        let stat = fs::read_to_string("/proc/stat").ok()?;
+        let fields: Vec<u64> = stat
+            .lines()
+            .next()?
+            .split_whitespace()
+            .skip(1)
+            .take(8)
+            .map(|field| field.parse().unwrap_or(0))
+            .collect();
+
+        let total: u64 = fields.iter().sum();
+        let idle = fields.get(3).copied().unwrap_or(0) + fields.get(4).copied().unwrap_or(0);
+
+        // utime and stime follow the parenthesised command name
+        let own_stat = fs::read_to_string("/proc/self/stat").ok()?;
+        let own: u64 = own_stat[own_stat.rfind(')')? + 1..]
+            .split_whitespace()
+            .skip(11)
+            .take(2)
+            .map(|field| field.parse::<u64>().unwrap_or(0))
+            .sum();
+
+        Some(Self {
+            total,
+            busy: total.saturating_sub(idle),
+            own,
+        })
+    }
+}
+
+fn running_tasks() -> Option<u64> {
+    let loadavg = fs::read_to_string("/proc/loadavg").ok()?;
+    let running = loadavg.split_whitespace().nth(3)?.split('/').next()?;
+
+    running.parse().ok()
+}
@@ -0,0 +1,256 @@
+//! Live dashboard redrawn in place on the terminal, for mining by hand.
+
+use std::collections::{HashMap, VecDeque};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::estimate;
+use crate::history::{self, Status};
+use crate::job::Job;
+use crate::stats::{Rates, Snapshot, Stats};
+
+const REDRAW: Duration = Duration::from_secs(1);
+// Submission outcomes only change when someone runs `history submit`
+const LEDGER_POLL: Duration = Duration::from_secs(5);
+
+const SPARK_SAMPLES: usize = 60;
+const SPARKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+const BAR_WIDTH: usize = 30;
+// More threads than this are summarized on one line
+const MAX_BARS: usize = 16;
+const LOG_LINES: usize = 8;
+
+pub struct Dashboard {
+    log: Mutex<VecDeque<String>>,
+    // Nonces solved this session with the last status seen in the ledger
+    solves: Mutex<HashMap<u64, Status>>,
+    start: Instant,
+    stop: AtomicBool,
+    redraw: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl Dashboard {
+    /// Start redrawing on stdout, which must be a terminal.
+    pub fn start(job: Job, stats: Arc<Stats>, window: u64, history: Option<PathBuf>) -> Arc<Self> {
+        let dashboard = Arc::new(Self {
+            log: Mutex::new(VecDeque::new()),
+            solves: Mutex::new(HashMap::new()),
+            start: Instant::now(),
+            stop: AtomicBool::new(false),
+            redraw: Mutex::new(None),
+        });
+
+        let handle = thread::spawn({
+            let dashboard = dashboard.clone();
+
+            move || {
+                let mut screen = Screen::new(&job, &stats, window);
+                let mut polled = Instant::now();
+
+                loop {
+                    let stopping = dashboard.stop.load(Ordering::Relaxed);
+
+                    screen.sample();
+                    screen.draw(&dashboard.log.lock().unwrap());
+
+                    if stopping {
+                        break;
+                    }
+
+                    if let Some(path) = history.as_deref().filter(|_| polled.elapsed() >= LEDGER_POLL) {
+                        dashboard.poll_ledger(path);
+                        polled = Instant::now();
+                    }
+
+                    // Sleep in slices so `finish` does not wait out a whole redraw
+                    let slept = Instant::now();
+
+                    while slept.elapsed() < REDRAW && !dashboard.stop.load(Ordering::Relaxed) {
+                        thread::sleep(Duration::from_millis(50));
+                    }
+                }
+            }
+        });
+
+        *dashboard.redraw.lock().unwrap() = Some(handle);
+
+        // Anything written to stderr would tear the frame, so logs go in the pane
+        crate::logging::divert({
+            let dashboard = dashboard.clone();
+            move |line| dashboard.log(line)
+        });
+
+        dashboard
+    }
+
+    pub fn log(&self, line: impl Into<String>) {
+        let mut log = self.log.lock().unwrap();
+
+        log.push_back(format!("{:>8}  {}", estimate::format_duration(self.start.elapsed().as_secs_f64()), line.into()));
+
+        if log.len() > LOG_LINES {
+            log.pop_front();
+        }
+    }
+
+    /// Log a solution and watch the ledger for its submission outcome.
+    pub fn solved(&self, nonce: u64, hash: &str) {
+        self.solves.lock().unwrap().insert(nonce, Status::Pending);
+        self.log(format!("Solved with nonce {}, hash {}", nonce, hash));
+    }
+
+    /// Draw the last frame and stop, leaving the cursor below it.
+    pub fn finish(&self) {
+        self.stop.store(true, Ordering::Relaxed);
+        crate::logging::restore();
+
+        if let Some(handle) = self.redraw.lock().unwrap().take() {
+            let _ = handle.join();
+        }
+    }
+
+    fn poll_ledger(&self, path: &std::path::Path) {
+        let Ok(solves) = history::load(path) else {
+            return;
+        };
+
+        let mut changes = vec![];
+
+        {
+            let mut ours = self.solves.lock().unwrap();
+
+            for solve in solves {
+                if let Some(status) = ours.get_mut(&solve.nonce) {
+                    if *status != solve.status {
+                        *status = solve.status;
+                        changes.push(format!("Nonce {} {}", solve.nonce, solve.status));
+                    }
+                }
+            }
+        }
+
+        for change in changes {
+            self.log(change);
+        }
+    }
+}
+
+// Rates measured by the dashboard itself, so it does not depend on the reporter
+struct Screen<'a> {
+    job: &'a Job,
+    stats: &'a Stats,
+    window: u64,
+    last: Snapshot,
+    rates: Rates,
+    history: VecDeque<f64>,
+    lines: usize,
+}
+
+impl<'a> Screen<'a> {
+    fn new(job: &'a Job, stats: &'a Stats, window: u64) -> Self {
+        Self {
+            job,
+            stats,
+            window,
+            last: stats.snapshot(),
+            rates: Rates::default(),
+            history: VecDeque::with_capacity(SPARK_SAMPLES),
+            lines: 0,
+        }
+    }
+
+    fn sample(&mut self) {
+        let now = self.stats.snapshot();
+        self.rates.update(&self.last, &now);
+        self.history.push_back(self.rates.total);
+
+        if self.history.len() > SPARK_SAMPLES {
+            self.history.pop_front();
+        }
+
+        self.last = now;
+    }
+
+    fn draw(&mut self, log: &VecDeque<String>) {
+        let mut out = vec![];
+        let job = self.job;
+        let total = self.history.back().copied().unwrap_or(0.0);
+
+        out.push(format!(
+            "\x1b[1mBlock {}\x1b[0m  difficulty {}  message {:?}",
+            job.index, job.difficulty, job.message
+        ));
+        out.push(format!("Hashrate  {:>8.2} MH/s  {}", total / 1_000_000.0, self.sparkline()));
+
+        let fastest = self.rates.max_thread().max(1.0);
+
+        for (thread, rate) in self.rates.per_thread.iter().enumerate().take(MAX_BARS) {
+            let filled = (rate / fastest * BAR_WIDTH as f64).round() as usize;
+
+            out.push(format!(
+                "  {:>3} {}{} {:>6.2} MH/s",
+                thread,
+                "█".repeat(filled),
+                "░".repeat(BAR_WIDTH - filled),
+                rate / 1_000_000.0
+            ));
+        }
+
+        if self.rates.per_thread.len() > MAX_BARS {
+            out.push(format!("  ... and {} more threads", self.rates.per_thread.len() - MAX_BARS));
+        }
+
+        out.push(format!("Hashes    {}", self.last.total()));
+
+        out.push(match self.stats.best() {
+            Some((zeros, nonce)) => {
+                format!("Best      {} zeros, nonce {}, {}", zeros, nonce, hex::encode(job.hash(nonce)))
+            }
+            None => String::from("Best      -"),
+        });
+
+        out.push(format!(
+            "ETA       {} ({:.0}% within {})",
+            estimate::format_duration(estimate::eta(job.difficulty, self.rates.ema)),
+            estimate::solve_chance(job.difficulty, self.rates.ema, self.window as f64) * 100.0,
+            estimate::format_duration(self.window as f64)
+        ));
+
+        out.push(String::from("\x1b[1mLog\x1b[0m"));
+        out.extend(log.iter().map(|line| format!("  {}", line)));
+
+        let mut stdout = std::io::stdout().lock();
+
+        // Back over the previous frame and clear it, so the frame can shrink
+        if self.lines > 0 {
+            let _ = write!(stdout, "\x1b[{}F", self.lines);
+        }
+
+        let _ = write!(stdout, "\x1b[J");
+
+        for line in &out {
+            let _ = writeln!(stdout, "{}", line);
+        }
+
+        let _ = stdout.flush();
+
+        self.lines = out.len();
+    }
+
+    fn sparkline(&self) -> String {
+        let max = self.history.iter().copied().fold(0.0, f64::max);
+
+        if max <= 0.0 {
+            return String::new();
+        }
+
+        self.history
+            .iter()
+            .map(|rate| SPARKS[((rate / max) * (SPARKS.len() - 1) as f64).round() as usize])
+            .collect()
+    }
+}
@@ -0,0 +1,170 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::info;
+
+use crate::engine::Engine;
+use crate::history;
+use crate::job::Job;
+use crate::miner::{Miner, NonceRange, Order, DEFAULT_BATCH_SIZE};
+use crate::stats::Stats;
+use crate::throttle::Throttle;
+
+/// Smallest batch accepted, so batch counts always fit in a u64.
+pub const MIN_BATCH_SIZE: u64 = 1_000;
+
+const BATCH_SIZES: &[u64] = &[10_000, 50_000, 250_000, 1_000_000];
+const SAMPLE: Duration = Duration::from_millis(500);
+// Smaller batches stop sooner on a new block, so take the smallest within this
+// fraction of the best throughput
+const BATCH_TOLERANCE: f64 = 0.98;
+
+/// Fastest settings measured on this host.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Profile {
+    pub host: String,
+    pub cpus: usize,
+    pub engine: Engine,
+    pub threads: usize,
+    pub batch_size: u64,
+    /// Measured H/s with these settings.
+    pub hashrate: f64,
+}
+
+/// `profile.json` next to the default solution ledger.
+pub fn default_path() -> PathBuf {
+    history::default_path().with_file_name("profile.json")
+}
+
+/// The cached profile, if it was measured on this host with a usable engine.
+pub fn load(path: &Path) -> Option<Profile> {
+    let profile: Profile = serde_json::from_str(&fs::read_to_string(path).ok()?).ok()?;
+
+    let current = profile.host == history::hostname()
+        && profile.cpus == num_cpus::get()
+        && profile.batch_size >= MIN_BATCH_SIZE;
+
+    current.then_some(profile)
+}
+
+pub fn save(path: &Path, profile: &Profile) -> io::Result<()> {
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)?;
+    }
+
+    fs::write(path, serde_json::to_string_pretty(profile)?)
+}
+
+/// Pick the engine, then the thread count, then the batch size, one at a time.
+pub fn calibrate() -> Profile {
+    let cpus = num_cpus::get();
+    let threads = cpus.max(1);
+
+    info!("Calibrating {} engines on {} CPUs...", Engine::ALL.len(), cpus);
+
+    // Let clocks ramp up so the first candidate is not measured cold
+    sample(Engine::default(), threads, DEFAULT_BATCH_SIZE);
+
+    let (engine, _) = fastest(Engine::ALL.iter().map(|&engine| (engine, threads, DEFAULT_BATCH_SIZE)));
+
+    // Hyperthreads do not always pay for themselves
+    let mut counts = vec![threads];
+
+    if threads > 1 {
+        counts.push(threads / 2);
+    }
+
+    let (_, threads) = fastest(counts.into_iter().map(|threads| (engine, threads, DEFAULT_BATCH_SIZE)));
+
+    let rates: Vec<(u64, f64)> = BATCH_SIZES
+        .iter()
+        .map(|&batch_size| (batch_size, measure(engine, threads, batch_size)))
+        .collect();
+
+    let best = rates.iter().map(|(_, rate)| *rate).fold(0.0, f64::max);
+    let (batch_size, hashrate) = rates
+        .into_iter()
+        .find(|(_, rate)| *rate >= best * BATCH_TOLERANCE)
+        .unwrap();
+
+    Profile {
+        host: history::hostname(),
+        cpus,
+        engine,
+        threads,
+        batch_size,
+        hashrate,
+    }
+}
+
+// The candidate `(engine, threads, batch_size)` with the highest hashrate,
+// returning its engine and thread count
+fn fastest(candidates: impl Iterator<Item = (Engine, usize, u64)>) -> (Engine, usize) {
+    let candidates: Vec<_> = candidates.collect();
+
+    if let [(engine, threads, _)] = candidates[..] {
+        return (engine, threads);
+    }
+
+    candidates
+        .into_iter()
+        .map(|(engine, threads, batch_size)| (engine, threads, measure(engine, threads, batch_size)))
+        .max_by(|a, b| a.2.total_cmp(&b.2))
+        .map(|(engine, threads, _)| (engine, threads))
+        .unwrap()
+}
+
+fn measure(engine: Engine, threads: usize, batch_size: u64) -> f64 {
+    let rate = sample(engine, threads, batch_size);
+
+    info!(
+        "  {:<12} {:>3} threads, batch {:>9}: {:.2} MH/s",
+        engine.name(),
+        threads,
+        batch_size,
+        rate / 1_000_000.0
+    );
+
+    rate
+}
+
+// Hashes per second over one sampling period
+fn sample(engine: Engine, threads: usize, batch_size: u64) -> f64 {
+    let miner = Miner {
+        threads,
+        batch_size,
+        engine,
+        order: Order::Asc,
+        seed: 0,
+        throttle: Arc::new(Throttle::new(threads, 100, 0, false)),
+        stats: Arc::new(Stats::new(threads)),
+    };
+
+    let job = Job {
+        index: 0,
+        prev_hash: [0; 32],
+        difficulty: 0,
+        miner: [0; 32],
+        message: String::from(crate::MESSAGE),
+    };
+
+    let stop = AtomicBool::new(false);
+    let start = Instant::now();
+
+    thread::scope(|scope| {
+        scope.spawn(|| {
+            thread::sleep(SAMPLE);
+            stop.store(true, Ordering::Relaxed);
+        });
+
+        // No hash has this many zeros, so nothing is ever reported
+        miner.search(&job, NonceRange::FULL, usize::MAX, &|_| {}, &stop);
+    });
+
+    miner.stats.snapshot().total() as f64 / start.elapsed().as_secs_f64()
+}
@@ -0,0 +1,163 @@
+//! Follow the contract over RPC and turn each new block into a job, and talk
+//! to the configured endpoints directly.
+
+use clap::{Args, Subcommand};
+use serde_json::json;
+use std::io::Read;
+use std::path::PathBuf;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::info;
+
+use crate::error::MinerError;
+use crate::network::Network;
+use crate::rpc::{Client, Endpoints, Recorder, Replay, Transport, Watcher};
+
+#[derive(Args, Clone, Default)]
+pub struct RpcArgs {
+    /// Milliseconds between checks for contract events
+    #[arg(long, default_value_t = 1000)]
+    pub poll_interval: u64,
+
+    /// Seconds between latest-ledger checks of every endpoint (0 = off)
+    #[arg(long, default_value_t = 15)]
+    pub health_interval: u64,
+
+    /// Send transactions to every endpoint at once, keeping the first accepted
+    #[arg(long)]
+    pub race_send: bool,
+
+    /// Answer requests from a file written by --record instead of the network
+    #[arg(long, conflicts_with = "record")]
+    pub replay: Option<PathBuf>,
+
+    /// Append every RPC request and response to this file
+    #[arg(long)]
+    pub record: Option<PathBuf>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Check every endpoint and print its health as JSON
+    Status,
+    /// Submit a signed transaction envelope (base64 XDR, `-` for stdin)
+    Send {
+        envelope: String,
+
+        #[command(flatten)]
+        rpc: RpcArgs,
+    },
+}
+
+impl RpcArgs {
+    pub fn interval(&self) -> Duration {
+        Duration::from_millis(self.poll_interval)
+    }
+
+    /// The network's endpoints, which the config must have named, checked
+    /// in the background when there is more than one.
+    pub fn endpoints(&self, network: Option<&Network>) -> Result<Arc<Endpoints>, MinerError> {
+        let network = network.ok_or_else(|| {
+            MinerError::Config(String::from("no network configured, set network in --config or FCM_NETWORK"))
+        })?;
+
+        let endpoints = match &self.replay {
+            Some(path) => {
+                let replay: Box<dyn Transport> = Box::new(Replay::load(path)?);
+                Endpoints::with_transports(vec![(path.display().to_string(), replay)], self.race_send)
+            }
+            None => {
+                network.validate()?;
+                Endpoints::new(&network.rpc_urls, self.race_send)
+            }
+        };
+
+        let endpoints = Arc::new(endpoints);
+
+        if self.health_interval > 0 && network.rpc_urls.len() > 1 && self.replay.is_none() {
+            endpoints.spawn_health_checks(Duration::from_secs(self.health_interval));
+        }
+
+        Ok(endpoints)
+    }
+
+    pub fn client(&self, endpoints: &Arc<Endpoints>) -> Result<Client, MinerError> {
+        let transport: Box<dyn Transport> = match &self.record {
+            Some(path) => Box::new(Recorder::new(Box::new(endpoints.clone()), path)?),
+            None => Box::new(endpoints.clone()),
+        };
+
+        Ok(Client::new(transport))
+    }
+
+    /// A watcher on `network` and the endpoints it reads through.
+    pub fn watcher(&self, network: Option<&Network>) -> Result<(Watcher, Arc<Endpoints>), MinerError> {
+        let endpoints = self.endpoints(network)?;
+        let client = self.client(&endpoints)?;
+        let network = network.cloned().unwrap_or_default();
+
+        info!(
+            "Watching contract {} on {} through {} endpoint(s)",
+            network.contract_id.as_deref().unwrap_or_default(),
+            network.name,
+            endpoints.health().len()
+        );
+
+        Ok((Watcher::new(client, network)?, endpoints))
+    }
+}
+
+/// Print each new block as a job line, ready for `pool serve` or the daemon.
+pub fn run(args: &RpcArgs, network: Option<&Network>) -> Result<(), MinerError> {
+    let (mut watcher, _) = args.watcher(network)?;
+    let stop = AtomicBool::new(false);
+
+    let result = watcher.run(args.interval(), &stop, |block| {
+        info!(job = block.index, "Block {}, difficulty {}", block.index, block.difficulty);
+        println!("{}", block.to_json());
+    });
+
+    match result {
+        // A replay ends when its recorded responses run out
+        Err(MinerError::ReplayEnded(_)) => Ok(()),
+        result => result,
+    }
+}
+
+pub fn command(action: Command, network: Option<&Network>) -> Result<(), MinerError> {
+    match action {
+        Command::Status => {
+            let endpoints = RpcArgs::default().endpoints(network)?;
+            endpoints.check();
+
+            println!("{}", json!(endpoints.health()));
+        }
+        Command::Send { envelope, rpc } => {
+            let envelope = match envelope.as_str() {
+                "-" => {
+                    let mut envelope = String::new();
+                    std::io::stdin()
+                        .read_to_string(&mut envelope)
+                        .map_err(|err| MinerError::io("stdin".as_ref(), err))?;
+                    envelope.trim().to_string()
+                }
+                _ => envelope,
+            };
+
+            let endpoints = rpc.endpoints(network)?;
+
+            // Without a race, the freshest endpoint gets it first
+            if !rpc.race_send && endpoints.health().len() > 1 {
+                endpoints.check();
+            }
+
+            let result = rpc.client(&endpoints)?.send_transaction(&envelope)?;
+            info!("sendTransaction: {}", result["status"].as_str().unwrap_or("unknown"));
+
+            println!("{}", result);
+        }
+    }
+
+    Ok(())
+}
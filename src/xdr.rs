@@ -0,0 +1,253 @@
+//! Just enough Stellar XDR to name the FCM contract's ledger entries and read
+//! them back; every value is big endian and padded to four bytes.
+
+// ScVal discriminants
+const SCV_U64: u32 = 5;
+const SCV_SYMBOL: u32 = 15;
+const SCV_VEC: u32 = 16;
+const SCV_LEDGER_KEY_CONTRACT_INSTANCE: u32 = 20;
+
+const LEDGER_ENTRY_CONTRACT_DATA: u32 = 6;
+const SC_ADDRESS_CONTRACT: u32 = 1;
+const DURABILITY_PERSISTENT: u32 = 1;
+
+#[derive(Default)]
+pub struct Writer {
+    pub bytes: Vec<u8>,
+}
+
+impl Writer {
+    pub fn u32(&mut self, value: u32) -> &mut Self {
+        self.bytes.extend_from_slice(&value.to_be_bytes());
+        self
+    }
+
+    pub fn u64(&mut self, value: u64) -> &mut Self {
+        self.bytes.extend_from_slice(&value.to_be_bytes());
+        self
+    }
+
+    /// Fixed-length opaque data, such as a hash.
+    pub fn fixed(&mut self, data: &[u8]) -> &mut Self {
+        self.bytes.extend_from_slice(data);
+        self.pad(data.len())
+    }
+
+    /// Length-prefixed opaque data or string.
+    pub fn var(&mut self, data: &[u8]) -> &mut Self {
+        self.u32(data.len() as u32);
+        self.fixed(data)
+    }
+
+    fn pad(&mut self, len: usize) -> &mut Self {
+        self.bytes.resize(self.bytes.len() + (4 - len % 4) % 4, 0);
+        self
+    }
+}
+
+/// `LedgerKey::ContractData` for the persistent entry of `contract` at the
+/// ScVal already written as `key`.
+pub fn contract_data_key(contract: &[u8; 32], key: &[u8]) -> Vec<u8> {
+    let mut out = Writer::default();
+    out.u32(LEDGER_ENTRY_CONTRACT_DATA).u32(SC_ADDRESS_CONTRACT).fixed(contract);
+    out.bytes.extend_from_slice(key);
+    out.u32(DURABILITY_PERSISTENT);
+    out.bytes
+}
+
+/// The contract's instance entry, which holds its current block index.
+pub fn instance_key(contract: &[u8; 32]) -> Vec<u8> {
+    let mut key = Writer::default();
+    key.u32(SCV_LEDGER_KEY_CONTRACT_INSTANCE);
+
+    contract_data_key(contract, &key.bytes)
+}
+
+/// `Block(index)`, the entry holding one block's hash.
+pub fn block_key(contract: &[u8; 32], index: u64) -> Vec<u8> {
+    let mut key = Writer::default();
+    // vec![Symbol("Block"), U64(index)], the vec being optional in XDR
+    key.u32(SCV_VEC).u32(1).u32(2);
+    key.u32(SCV_SYMBOL).var(b"Block");
+    key.u32(SCV_U64).u64(index);
+
+    contract_data_key(contract, &key.bytes)
+}
+
+/// The ScVal shapes the FCM contract stores; anything else is skipped over
+/// as `Other`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ScVal {
+    Bool(bool),
+    Void,
+    U32(u32),
+    I32(i32),
+    U64(u64),
+    I64(i64),
+    Bytes(Vec<u8>),
+    String(String),
+    Symbol(String),
+    Vec(Vec<ScVal>),
+    Map(Vec<(ScVal, ScVal)>),
+    /// A contract instance's storage.
+    Instance(Vec<(ScVal, ScVal)>),
+    Other,
+}
+
+impl ScVal {
+    /// Value under the symbol `key`, for maps and instance storage.
+    pub fn get(&self, key: &str) -> Option<&ScVal> {
+        match self {
+            ScVal::Map(entries) | ScVal::Instance(entries) => entries
+                .iter()
+                .find(|(name, _)| matches!(name, ScVal::Symbol(name) if name == key))
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    pub fn as_u64(&self) -> Option<u64> {
+        match *self {
+            ScVal::U32(value) => Some(value as u64),
+            ScVal::U64(value) => Some(value),
+            ScVal::I32(value) => u64::try_from(value).ok(),
+            ScVal::I64(value) => u64::try_from(value).ok(),
+            _ => None,
+        }
+    }
+}
+
+pub struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        if self.bytes.len() < len {
+            return Err(String::from("XDR ends early"));
+        }
+
+        let (head, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+
+        Ok(head)
+    }
+
+    pub fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_be_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    pub fn u64(&mut self) -> Result<u64, String> {
+        Ok(u64::from_be_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    pub fn fixed(&mut self, len: usize) -> Result<&'a [u8], String> {
+        let data = self.take(len)?;
+        self.take((4 - len % 4) % 4)?;
+        Ok(data)
+    }
+
+    pub fn var(&mut self) -> Result<&'a [u8], String> {
+        let len = self.u32()? as usize;
+        self.fixed(len)
+    }
+
+    fn address(&mut self) -> Result<(), String> {
+        match self.u32()? {
+            // Account (a key type, then the key), claimable balance (likewise)
+            0 | 3 => self.fixed(36).map(|_| ()),
+            // Contract, liquidity pool
+            1 | 4 => self.fixed(32).map(|_| ()),
+            // Muxed account
+            2 => self.fixed(40).map(|_| ()),
+            other => Err(format!("unknown ScAddress type {}", other)),
+        }
+    }
+
+    fn map(&mut self) -> Result<Vec<(ScVal, ScVal)>, String> {
+        let len = self.u32()?;
+        let mut entries = Vec::with_capacity(len.min(256) as usize);
+
+        for _ in 0..len {
+            entries.push((self.scval()?, self.scval()?));
+        }
+
+        Ok(entries)
+    }
+
+    pub fn scval(&mut self) -> Result<ScVal, String> {
+        let text = |data: &[u8]| String::from_utf8_lossy(data).into_owned();
+
+        Ok(match self.u32()? {
+            0 => ScVal::Bool(self.u32()? != 0),
+            1 => ScVal::Void,
+            // Error: a type and a code
+            2 => self.fixed(8).map(|_| ScVal::Other)?,
+            3 => ScVal::U32(self.u32()?),
+            4 => ScVal::I32(self.u32()? as i32),
+            5 => ScVal::U64(self.u64()?),
+            6 => ScVal::I64(self.u64()? as i64),
+            // Timepoint, duration
+            7 | 8 => self.fixed(8).map(|_| ScVal::Other)?,
+            // U128, I128
+            9 | 10 => self.fixed(16).map(|_| ScVal::Other)?,
+            // U256, I256
+            11 | 12 => self.fixed(32).map(|_| ScVal::Other)?,
+            13 => ScVal::Bytes(self.var()?.to_vec()),
+            14 => ScVal::String(text(self.var()?)),
+            15 => ScVal::Symbol(text(self.var()?)),
+            16 => match self.u32()? {
+                0 => ScVal::Vec(vec![]),
+                _ => {
+                    let len = self.u32()?;
+                    let mut items = Vec::with_capacity(len.min(256) as usize);
+
+                    for _ in 0..len {
+                        items.push(self.scval()?);
+                    }
+
+                    ScVal::Vec(items)
+                }
+            },
+            17 => match self.u32()? {
+                0 => ScVal::Map(vec![]),
+                _ => ScVal::Map(self.map()?),
+            },
+            18 => self.address().map(|_| ScVal::Other)?,
+            19 => {
+                // Wasm hash or the built-in asset contract
+                if self.u32()? == 0 {
+                    self.fixed(32)?;
+                }
+
+                match self.u32()? {
+                    0 => ScVal::Instance(vec![]),
+                    _ => ScVal::Instance(self.map()?),
+                }
+            }
+            20 => ScVal::Other,
+            21 => self.fixed(8).map(|_| ScVal::Other)?,
+            other => return Err(format!("unknown ScVal type {}", other)),
+        })
+    }
+
+    /// The value of a `LedgerEntryData::ContractData`, as `getLedgerEntries`
+    /// returns it.
+    pub fn contract_data(&mut self) -> Result<ScVal, String> {
+        if self.u32()? != LEDGER_ENTRY_CONTRACT_DATA {
+            return Err(String::from("not a contract data entry"));
+        }
+
+        // Extension point, contract, key and durability come before the value
+        self.u32()?;
+        self.address()?;
+        self.scval()?;
+        self.u32()?;
+
+        self.scval()
+    }
+}
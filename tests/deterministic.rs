@@ -0,0 +1,72 @@
+//! Pinned solutions: `--deterministic` must find the lowest qualifying nonce,
+//! on any host, without calibrating or touching the tuning profile.
+
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+const PREV_HASH: &str = "00000000ba94a25be3e2d0cdb1ef390342efbf2913f9ebf362a5cc98efe37ddf";
+
+fn mine(name: &str, args: &[&str]) -> (Output, PathBuf) {
+    mine_with(name, args, &[])
+}
+
+fn mine_with(name: &str, args: &[&str], env: &[(&str, &str)]) -> (Output, PathBuf) {
+    let profile = std::env::temp_dir().join(format!("fcm-miner-test-{}-{}.json", name, std::process::id()));
+
+    let output = Command::new(env!("CARGO_BIN_EXE_fcm-miner-rust"))
+        .args(["--deterministic", "--no-history", "-i", "1", "-p", PREV_HASH, "-t", "4"])
+        .args(args)
+        .env("FCM_PROFILE", &profile)
+        .env_remove("FCM_CONFIG")
+        .env_remove("FCM_MESSAGE")
+        .env_remove("FCM_MINER")
+        .env_remove("FCM_WORKER")
+        .envs(env.iter().copied())
+        .output()
+        .unwrap();
+
+    (output, profile)
+}
+
+fn stdout(output: &Output) -> &str {
+    std::str::from_utf8(&output.stdout).unwrap().trim()
+}
+
+#[test]
+fn finds_the_lowest_nonce() {
+    let (output, profile) = mine("kale", &["--message", "KALE"]);
+
+    assert!(output.status.success());
+    assert_eq!(
+        stdout(&output),
+        r#"[28733,"0000108c400b2803bc60a232835b376e4d11003a42fd51e39224d497257d1541","KALE"]"#
+    );
+    assert!(!profile.exists(), "deterministic mode wrote a tuning profile");
+}
+
+#[test]
+fn pads_odd_length_messages() {
+    let (output, _) = mine("padding", &["--message", "it's x"]);
+
+    assert!(output.status.success());
+    assert_eq!(
+        stdout(&output),
+        r#"[251917,"00007426c247b817504245640793d3de7d3d20f0beff6fc74360bbb5f45af853","it's x"]"#
+    );
+}
+
+#[test]
+fn exhausts_just_below_the_solution() {
+    let (output, _) = mine("exhausted", &["--message", "KALE", "--nonce-end", "28732"]);
+
+    assert_eq!(output.status.code(), Some(6));
+    assert_eq!(stdout(&output), r#"{"end":28732,"exhausted":true,"hashes":28733,"start":0}"#);
+}
+
+#[test]
+fn environment_beats_flags() {
+    let (output, _) = mine_with("env", &["--message", "KALE"], &[("FCM_MESSAGE", "it's x")]);
+
+    assert!(output.status.success());
+    assert!(stdout(&output).starts_with(r#"[251917,"#));
+}
@@ -0,0 +1,153 @@
+//! The C ABI driven the way a foreign caller would, through raw pointers.
+
+use std::ffi::CString;
+use std::ptr;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use fcm_miner_rust::ffi::*;
+
+const PREV_HASH: &str = "00000000ba94a25be3e2d0cdb1ef390342efbf2913f9ebf362a5cc98efe37ddf";
+const SOLUTION: &str = "0000108c400b2803bc60a232835b376e4d11003a42fd51e39224d497257d1541";
+const TIMEOUT: Duration = Duration::from_secs(60);
+
+fn job(difficulty: u32) -> *mut FcmJob {
+    let prev_hash = hex::decode(PREV_HASH).unwrap();
+    let miner = CString::new(fcm_miner_rust::MINER).unwrap();
+    let message = CString::new("KALE").unwrap();
+
+    unsafe { fcm_job_new(1, prev_hash.as_ptr(), difficulty, miner.as_ptr(), message.as_ptr()) }
+}
+
+fn empty_event() -> FcmEvent {
+    FcmEvent {
+        kind: 0,
+        zeros: 0,
+        nonce: 0,
+        hashes: 0,
+        rate: 0.0,
+        hash: [0; 32],
+    }
+}
+
+// Every event until the search is over and drained
+fn drain(miner: *mut FcmMiner) -> Vec<FcmEvent> {
+    let started = Instant::now();
+    let mut events = vec![];
+
+    loop {
+        let mut event = empty_event();
+
+        match unsafe { fcm_poll_event(miner, &mut event) } {
+            1 => events.push(event),
+            0 => thread::sleep(Duration::from_millis(10)),
+            _ => return events,
+        }
+
+        assert!(started.elapsed() < TIMEOUT, "search did not finish");
+    }
+}
+
+#[test]
+fn job_new_rejects_bad_arguments() {
+    let prev_hash = hex::decode(PREV_HASH).unwrap();
+    let miner = CString::new(fcm_miner_rust::MINER).unwrap();
+    let bad_miner = CString::new("GBDVX4VELCDSQ54KQJYTNHXAHFLBCA77ZY2USQBM4CSHTTV7DME7KALF").unwrap();
+    let message = CString::new("KALE").unwrap();
+
+    unsafe {
+        assert!(fcm_job_new(1, ptr::null(), 4, miner.as_ptr(), message.as_ptr()).is_null());
+        assert!(fcm_job_new(1, prev_hash.as_ptr(), 4, ptr::null(), message.as_ptr()).is_null());
+        assert!(fcm_job_new(1, prev_hash.as_ptr(), 4, miner.as_ptr(), ptr::null()).is_null());
+        assert!(fcm_job_new(1, prev_hash.as_ptr(), 4, bad_miner.as_ptr(), message.as_ptr()).is_null());
+        assert!(fcm_job_new(1, prev_hash.as_ptr(), 33, miner.as_ptr(), message.as_ptr()).is_null());
+
+        let job = fcm_job_new(1, prev_hash.as_ptr(), 32, miner.as_ptr(), message.as_ptr());
+        assert!(!job.is_null());
+        fcm_job_free(job);
+    }
+}
+
+#[test]
+fn mines_until_the_solution() {
+    let job = job(4);
+
+    unsafe {
+        let miner = fcm_mine_start(job, 1, 0, u64::MAX);
+        assert!(!miner.is_null());
+
+        let events = drain(miner);
+        let solutions: Vec<_> = events.iter().filter(|event| event.kind == FCM_EVENT_SOLUTION).collect();
+
+        // One thread searches upwards, so the lowest solution is the one found
+        assert_eq!(solutions.len(), 1);
+        assert_eq!(solutions[0].nonce, 28733);
+        assert_eq!(solutions[0].zeros, 4);
+        assert_eq!(hex::encode(solutions[0].hash), SOLUTION);
+        assert!(solutions[0].hashes > 28733);
+
+        let bests = events.iter().filter(|event| event.kind == FCM_EVENT_BEST);
+        assert!(bests.clone().any(|event| event.nonce == 28733));
+        assert!(bests.clone().zip(bests.skip(1)).all(|(before, after)| after.zeros > before.zeros));
+
+        fcm_miner_free(miner);
+        fcm_job_free(job);
+    }
+}
+
+#[test]
+fn cancel_stops_the_search() {
+    let job = job(32);
+
+    unsafe {
+        let miner = fcm_mine_start(job, 1, 0, u64::MAX);
+        assert!(!miner.is_null());
+
+        thread::sleep(Duration::from_millis(100));
+        fcm_cancel(miner);
+
+        let events = drain(miner);
+        assert!(events.iter().all(|event| event.kind != FCM_EVENT_SOLUTION && event.kind != FCM_EVENT_EXHAUSTED));
+
+        let mut event = empty_event();
+        assert_eq!(fcm_poll_event(miner, &mut event), -1);
+
+        fcm_miner_free(miner);
+        fcm_job_free(job);
+    }
+}
+
+#[test]
+fn verify_reports_all_three_cases() {
+    let job = job(4);
+    let mut hash = [0u8; 32];
+
+    unsafe {
+        assert_eq!(fcm_verify(job, 28733, hash.as_mut_ptr()), 1);
+        assert_eq!(hex::encode(hash), SOLUTION);
+
+        assert_eq!(fcm_verify(job, 28732, hash.as_mut_ptr()), 0);
+        assert_eq!(fcm_verify(job, 28732, ptr::null_mut()), 0);
+
+        assert_eq!(fcm_verify(ptr::null(), 28733, hash.as_mut_ptr()), -1);
+
+        fcm_job_free(job);
+    }
+}
+
+#[test]
+fn free_while_running() {
+    let job = job(32);
+
+    unsafe {
+        let miner = fcm_mine_start(job, 2, 0, u64::MAX);
+        assert!(!miner.is_null());
+
+        thread::sleep(Duration::from_millis(100));
+        fcm_miner_free(miner);
+        fcm_job_free(job);
+    }
+
+    // The workers wind down on their own once the handle is gone
+    thread::sleep(Duration::from_millis(200));
+}
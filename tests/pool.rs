@@ -0,0 +1,148 @@
+//! A pool on localhost with two workers: leases must not overlap, and the
+//! block must be split over the shares both of them found.
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const PREV_HASH: &str = "00000000ba94a25be3e2d0cdb1ef390342efbf2913f9ebf362a5cc98efe37ddf";
+const LEASE_SIZE: u64 = 4096;
+const TIMEOUT: Duration = Duration::from_secs(120);
+
+// Kills the process even when an assertion fails first
+struct Process(Child);
+
+impl Drop for Process {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+fn command(profile: &Path) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_fcm-miner-rust"));
+    command.arg("--no-history").env("FCM_PROFILE", profile).env_remove("FCM_CONFIG").env_remove("FCM_POOL_TOKEN");
+    command
+}
+
+fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("fcm-miner-test-pool-{}-{}", std::process::id(), name))
+}
+
+fn free_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port()
+}
+
+// Blocks until the pool logs a line containing `needle`
+fn wait_for(lines: &mpsc::Receiver<String>, needle: &str) {
+    let started = Instant::now();
+
+    while started.elapsed() < TIMEOUT {
+        if let Ok(line) = lines.recv_timeout(Duration::from_millis(100)) {
+            if line.contains(needle) {
+                return;
+            }
+        }
+    }
+
+    panic!("pool never logged {:?}", needle);
+}
+
+#[test]
+fn two_workers_split_the_block() {
+    let shares = temp_path("shares.jsonl");
+    let profile = temp_path("profile.json");
+    let listen = format!("127.0.0.1:{}", free_port());
+    let _ = std::fs::remove_file(&shares);
+
+    let mut pool = Process(
+        command(&profile)
+            .args(["pool", "serve", "--listen", &listen, "--share-zeros", "2"])
+            .args(["--lease-size", &LEASE_SIZE.to_string()])
+            .arg("--shares")
+            .arg(&shares)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap(),
+    );
+
+    let (tx, lines) = mpsc::channel();
+    let stderr = BufReader::new(pool.0.stderr.take().unwrap());
+    thread::spawn(move || stderr.lines().map_while(Result::ok).for_each(|line| drop(tx.send(line))));
+    wait_for(&lines, "Pool listening");
+
+    let _workers: Vec<Process> = ["w1", "w2"]
+        .into_iter()
+        .map(|name| {
+            let worker = command(&profile)
+                .args(["worker", "--pool", &listen, "--name", name])
+                .args(["-j", "1", "--batch-size", "1000", "--engine", "tiny-keccak"])
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .spawn()
+                .unwrap();
+
+            wait_for(&lines, &format!("Worker {} connected", name));
+            Process(worker)
+        })
+        .collect();
+
+    // Only now is there a job, so both workers lease from the start
+    let job = format!(r#"{{"index":1,"prev_hash":"{}","difficulty":4,"message":"KALE"}}"#, PREV_HASH);
+    writeln!(pool.0.stdin.as_mut().unwrap(), "{}", job).unwrap();
+
+    let (tx, solution) = mpsc::channel();
+    let stdout = BufReader::new(pool.0.stdout.take().unwrap());
+    thread::spawn(move || stdout.lines().map_while(Result::ok).for_each(|line| drop(tx.send(line))));
+
+    assert_eq!(
+        solution.recv_timeout(TIMEOUT).expect("pool found no solution"),
+        r#"[28733,"0000108c400b2803bc60a232835b376e4d11003a42fd51e39224d497257d1541","KALE"]"#
+    );
+    drop(pool);
+
+    // Leases start at zero and follow each other, so every lease holds shares of one worker
+    let ledger = std::fs::read_to_string(&shares).unwrap();
+    let mut owners: HashMap<u64, String> = HashMap::new();
+
+    for entry in ledger.lines().map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap()) {
+        if entry["type"] != "share" {
+            continue;
+        }
+
+        let lease = entry["nonce"].as_u64().unwrap() / LEASE_SIZE;
+        let worker = entry["worker"].as_str().unwrap();
+        let owner = owners.entry(lease).or_insert_with(|| worker.to_string());
+        assert_eq!(owner, worker, "lease {} was mined by two workers", lease);
+    }
+
+    let output = command(&profile)
+        .args(["pool", "payouts", "--format", "json", "--shares"])
+        .arg(&shares)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let payouts: Vec<serde_json::Value> = serde_json::from_slice(&output.stdout).unwrap();
+    let fraction = |name: &str| {
+        payouts
+            .iter()
+            .filter(|payout| payout["worker"] == name)
+            .map(|payout| payout["fraction"].as_f64().unwrap())
+            .sum()
+    };
+    let (w1, w2): (f64, f64) = (fraction("w1"), fraction("w2"));
+
+    assert!(payouts.iter().all(|payout| payout["index"] == 1 && payout["nonce"] == 28733));
+    assert!(w1 > 0.0 && w2 > 0.0, "payouts {:?}", payouts);
+    assert!((w1 + w2 - 1.0).abs() < 1e-9, "payouts {:?}", payouts);
+
+    let _ = std::fs::remove_file(&shares);
+}
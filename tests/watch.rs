@@ -0,0 +1,99 @@
+//! The watcher against recorded RPC responses. The fixtures were generated
+//! with stellar-xdr 21.2, so the XDR is decoded independently of our encoder,
+//! and they carry the params each request must be sent with.
+
+use std::path::Path;
+
+use fcm_miner_rust::error::MinerError;
+use fcm_miner_rust::network::{self, Network};
+use fcm_miner_rust::rpc::{Block, Client, Replay, Watcher};
+use fcm_miner_rust::xdr::ScVal;
+
+fn watcher(fixture: &str) -> Watcher {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(fixture);
+    let client = Client::new(Box::new(Replay::load(&path).unwrap()));
+
+    Watcher::new(client, network()).unwrap()
+}
+
+fn network() -> Network {
+    Network {
+        name: String::from("test"),
+        passphrase: String::from(network::TESTNET_PASSPHRASE),
+        rpc_urls: vec![String::from("http://localhost:8000")],
+        contract_id: Some(String::from("CC5TSJ3E26YUYGYQKOBNJQLPX4XMUHUY7Q26JX53CJ2YUIZB5HVXXRV6")),
+    }
+}
+
+fn block(index: u64, hash: u8, difficulty: usize) -> Block {
+    Block {
+        index,
+        prev_hash: [hash; 32],
+        difficulty,
+    }
+}
+
+#[test]
+fn follows_events_and_falls_back_to_the_ledger() {
+    let mut watcher = watcher("watch.jsonl");
+
+    // The first round reads the ledger whether or not anything happened
+    assert_eq!(watcher.poll().unwrap(), Some(block(6, 0xab, 6)));
+
+    // No events, then only an unrelated one: the ledger is not read again
+    assert_eq!(watcher.poll().unwrap(), None);
+    assert_eq!(watcher.poll().unwrap(), None);
+
+    // A mine event switches the job
+    assert_eq!(watcher.poll().unwrap(), Some(block(7, 0xcd, 7)));
+
+    // Another one that leaves the block as it was
+    assert_eq!(watcher.poll().unwrap(), None);
+
+    // getEvents fails, so the ledger is read directly
+    assert_eq!(watcher.poll().unwrap(), Some(block(8, 0xef, 8)));
+
+    // The cursor was dropped, so events start over from the latest ledger
+    assert_eq!(watcher.poll().unwrap(), None);
+
+    assert!(matches!(watcher.poll(), Err(MinerError::ReplayEnded(_))));
+}
+
+#[test]
+fn reads_the_block_from_contract_data() {
+    let watcher = watcher("block.jsonl");
+
+    assert_eq!(watcher.current_block().unwrap(), block(42, 0x12, 9));
+}
+
+#[test]
+fn decodes_instance_and_block_entries() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/block.jsonl");
+    let client = Client::new(Box::new(Replay::load(&path).unwrap()));
+    let network = network();
+
+    let instance = client.contract_data(&[network.instance_key().unwrap()]).unwrap().remove(0).unwrap();
+    assert!(matches!(instance, ScVal::Instance(_)));
+
+    let state = instance.get("FarmBlock").unwrap();
+    assert_eq!(state.get("current").and_then(ScVal::as_u64), Some(41));
+    assert_eq!(state.get("difficulty").and_then(ScVal::as_u64), Some(9));
+    assert_eq!(state.get("is_nuked"), Some(&ScVal::Bool(false)));
+
+    let block = client.contract_data(&[network.block_key(41).unwrap()]).unwrap().remove(0).unwrap();
+    assert_eq!(block.get("hash"), Some(&ScVal::Bytes(vec![0x12; 32])));
+    assert_eq!(block.get("timestamp").and_then(ScVal::as_u64), Some(1));
+
+    // Missing entries come back as None
+    assert_eq!(client.contract_data(&[network.block_key(42).unwrap()]).unwrap(), vec![None]);
+}
+
+#[test]
+fn replay_checks_params() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/block.jsonl");
+    let client = Client::new(Box::new(Replay::load(&path).unwrap()));
+
+    // The recording expects the instance key first
+    let wrong = network().block_key(41).unwrap();
+    assert!(matches!(client.contract_data(&[wrong]), Err(MinerError::Rpc(_))));
+}